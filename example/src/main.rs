@@ -1,8 +1,9 @@
-use once_cell::sync::Lazy;
 use rest_model::{
     Doc,
     method::{Init, Put},
+    openapi::openapi,
     rest_model,
+    typescript::typescript,
 };
 use rest_model_postgres::Db;
 use serde::{Deserialize, Serialize};
@@ -10,14 +11,6 @@ use serde::{Deserialize, Serialize};
 const DB_NAME: &str = "mydb.public";
 const TABLE_NAME: &str = "guest";
 
-static TB_NAME: Lazy<String> = Lazy::new(|| {
-    std::env::var("TABLE_NAME").unwrap_or(TABLE_NAME.to_string())
-});
-
-fn get_table_name() -> &'static str {
-    TB_NAME.as_str()
-}
-
 #[rest_model(db(Db, DB_NAME, TABLE_NAME), with(all))]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Guest {
@@ -27,12 +20,23 @@ pub struct Guest {
 // tokio main
 #[tokio::main]
 async fn main() {
+    // `cargo run -- typescript > guest.ts` / `cargo run -- openapi > guest.json`
+    match std::env::args().nth(1).as_deref() {
+        Some("typescript") => return print!("{}", typescript::<Guest>()),
+        Some("openapi") => return println!("{:#}", openapi::<Guest>("guest", "0.1.0")),
+        _ => {}
+    }
     let uri = &std::env::var("DATABASE_URL").unwrap();
     let client = Db::try_new(uri).await.unwrap();
+    // each tenant gets its own schema: `mydb.<TENANT>.guest`
+    let client = match std::env::var("TENANT") {
+        Ok(tenant) => client.with_schema(&tenant).unwrap(),
+        Err(_) => client,
+    };
     Guest::init(&client).await.unwrap();
     Guest::put(
         &client,
-        &vec![
+        &[
             Doc::new(
                 &client,
                 Guest {
@@ -0,0 +1,248 @@
+use std::{
+    io::{BufRead, Write},
+    path::PathBuf,
+    sync::OnceLock,
+};
+
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand};
+use rest_model::{
+    export,
+    import::{self, ImportMode, ImportParams},
+    method::{Delete, Get, GetWithId, Init, Patch},
+    pagination::PaginationParams,
+    Condition, DeleteParams, PatchParams, RestModel,
+};
+use rest_model_postgres::Db;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+
+/// Inspect and maintain rest_model tables without hand-written SQL.
+///
+/// Filters are `Condition` JSON and sorts are `+field`/`-field`, exactly as
+/// in `PaginationParams`, e.g. `--filter '{"Gt": ["age", 18]}' --sort -age`.
+#[derive(Parser)]
+#[command(name = "rest-model", version)]
+struct Cli {
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: String,
+    /// `[database.]schema` holding the table, as in `#[rest_model(db(..))]`.
+    #[arg(long, default_value = "public")]
+    db: String,
+    #[arg(long)]
+    table: String,
+    /// A tenant's schema, replacing the last segment of `--db`.
+    #[arg(long)]
+    schema: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the schema and table if they don't exist.
+    Init,
+    /// Print one page of documents with pagination info.
+    List {
+        #[arg(long)]
+        filter: Option<String>,
+        #[arg(long, allow_hyphen_values = true)]
+        sort: Option<String>,
+        #[arg(long)]
+        page: Option<u32>,
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+    /// Print one document.
+    Get { id: String },
+    /// Set top-level fields of every matching document.
+    Patch {
+        #[arg(long)]
+        filter: String,
+        /// JSON object of fields to set.
+        #[arg(long)]
+        patch: String,
+    },
+    /// Delete every matching document, after confirming the count.
+    ///
+    /// The CLI doesn't know the models, so references from other tables are
+    /// left alone: no `restrict` check, `cascade` or `set_null`.
+    Delete {
+        #[arg(long)]
+        filter: String,
+        /// Don't ask for confirmation.
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Write matching documents as NDJSON.
+    Export {
+        #[arg(long)]
+        filter: Option<String>,
+        #[arg(long, allow_hyphen_values = true)]
+        sort: Option<String>,
+        /// Defaults to stdout.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Upsert documents from NDJSON, as written by `export` or bare `data` objects.
+    Import {
+        /// Defaults to stdin.
+        #[arg(long, short)]
+        input: Option<PathBuf>,
+        #[arg(long)]
+        batch_size: Option<u32>,
+        /// Bulk-load with `COPY`; lines whose `_id` already exists fail.
+        #[arg(long)]
+        copy: bool,
+    },
+}
+
+/// Whatever JSON the table named on the command line holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+struct Document(Value);
+
+/// `(db_name, table_name)` from the command line, set before any query.
+static TABLE: OnceLock<(&'static str, &'static str)> = OnceLock::new();
+
+impl RestModel for Document {
+    fn get_db_name() -> &'static str {
+        TABLE.get().expect("table is not set").0
+    }
+
+    fn get_table_name() -> &'static str {
+        TABLE.get().expect("table is not set").1
+    }
+}
+
+impl Init<Document, Db> for Document {}
+impl Get<Document, Db> for Document {}
+impl GetWithId<Document, Db> for Document {}
+impl Patch<Document, Db> for Document {}
+impl Delete<Document, Db> for Document {}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    TABLE.get_or_init(|| (cli.db.clone().leak(), cli.table.clone().leak()));
+    let client = Db::try_new(&cli.database_url).await?;
+    let client = match &cli.schema {
+        Some(schema) => client.with_schema(schema)?,
+        None => client,
+    };
+
+    match cli.command {
+        Command::Init => Document::init(&client).await?,
+        Command::List {
+            filter,
+            sort,
+            page,
+            limit,
+        } => {
+            let params = PaginationParams {
+                page,
+                limit,
+                sort,
+                filter: filter.as_deref().map(condition).transpose()?,
+                custom: None,
+                expand: None,
+                q: None,
+            };
+            print(&Document::get(&client, &params).await?)?;
+        }
+        Command::Get { id } => print(&Document::get_with_id(&client, &id).await?)?,
+        Command::Patch { filter, patch } => {
+            let params = PatchParams {
+                filter: condition(&filter)?,
+                patch: serde_json::from_str(&patch)?,
+            };
+            if !params.patch.is_object() {
+                bail!("--patch must be a JSON object");
+            }
+            print(&Document::patch(&client, &params).await?)?;
+        }
+        Command::Delete { filter, yes } => {
+            let filter = condition(&filter)?;
+            let matched = Document::get(
+                &client,
+                &PaginationParams {
+                    limit: Some(1),
+                    filter: Some(filter.clone()),
+                    ..Default::default()
+                },
+            )
+            .await?
+            .pagination
+            .total_count;
+            if matched == 0 {
+                eprintln!("No documents match.");
+                return Ok(());
+            }
+            let prompt = format!(
+                "Delete {} document(s)? References to them from other tables are not checked.",
+                matched
+            );
+            if !yes && !confirm(&prompt)? {
+                bail!("Aborted.");
+            }
+            print(&Document::delete(&client, &DeleteParams { filter }).await?)?;
+        }
+        Command::Export {
+            filter,
+            sort,
+            output,
+        } => {
+            let filter = filter.as_deref().map(condition).transpose()?;
+            let docs = client.stream::<Document>(filter.as_ref(), sort.as_deref())?;
+            let mut out: Box<dyn AsyncWrite + Unpin> = match output {
+                Some(path) => Box::new(tokio::fs::File::create(path).await?),
+                None => Box::new(tokio::io::stdout()),
+            };
+            let written = export::write_all(export::ndjson(docs), &mut out).await?;
+            eprintln!("Wrote {} bytes.", written);
+        }
+        Command::Import {
+            input,
+            batch_size,
+            copy,
+        } => {
+            let params = ImportParams {
+                batch_size,
+                mode: if copy {
+                    ImportMode::Copy
+                } else {
+                    ImportMode::Upsert
+                },
+            };
+            let input: Box<dyn AsyncRead + Unpin> = match input {
+                Some(path) => Box::new(tokio::fs::File::open(path).await?),
+                None => Box::new(tokio::io::stdin()),
+            };
+            let records = import::ndjson::<Document, _, _>(&client, BufReader::new(input));
+            let report = import::import(&client, records, &params).await?;
+            print(&report)?;
+            if !report.failed.is_empty() {
+                bail!("{} line(s) failed", report.failed.len());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn condition(json: &str) -> Result<Condition> {
+    Ok(serde_json::from_str(json)?)
+}
+
+fn print(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", question);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
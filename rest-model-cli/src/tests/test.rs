@@ -0,0 +1,85 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+use serde_json::{json, Value};
+
+/// Runs the CLI against `test_cli.people`, feeding `stdin`.
+fn cli(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rest-model"))
+        .args([
+            "--db",
+            "mydb.public",
+            "--schema",
+            "test_cli",
+            "--table",
+            "people",
+        ])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn json_out(output: Output) -> Value {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn admin() {
+    assert!(cli(&["init"], "").status.success());
+    let all = r#"{"Gte": ["age", 0]}"#;
+    assert!(cli(&["delete", "--filter", all, "--yes"], "")
+        .status
+        .success());
+
+    let people = "{\"_id\":\"67c707bc698b8e529f994690\",\"data\":{\"name\":\"Ann\",\"age\":30},\"_created_at\":0,\"_updated_at\":0}\n\
+                  {\"name\":\"Bob\",\"age\":20}\n\
+                  not json\n";
+    let output = cli(&["import", "--batch-size", "1"], people);
+    assert!(!output.status.success());
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["imported_count"], 2);
+    assert_eq!(report["failed"][0]["line"], 3);
+
+    let page = json_out(cli(&["list", "--sort", "-name", "--limit", "1"], ""));
+    assert_eq!(page["items"][0]["data"]["name"], "Bob");
+    assert_eq!(page["pagination"]["total_count"], 2);
+
+    let filter = r#"{"Eq": ["name", "Ann"]}"#;
+    let patched = json_out(cli(
+        &["patch", "--filter", filter, "--patch", r#"{"age": 31}"#],
+        "",
+    ));
+    assert_eq!(patched["updated_count"], 1);
+    let ann = json_out(cli(&["get", "67c707bc698b8e529f994690"], ""));
+    assert_eq!(ann["data"], json!({ "name": "Ann", "age": 31 }));
+
+    let output = cli(&["export", "--filter", filter], "");
+    let lines: Vec<Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["_id"], "67c707bc698b8e529f994690");
+
+    assert!(!cli(&["delete", "--filter", filter], "n\n").status.success());
+    let deleted = json_out(cli(&["delete", "--filter", filter], "y\n"));
+    assert_eq!(deleted["deleted_count"], 1);
+}
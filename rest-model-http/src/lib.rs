@@ -0,0 +1,195 @@
+use anyhow::{anyhow, Result};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use rest_model::{
+    oid::ObjectId,
+    pagination::{Pagination, PaginationParams},
+    Conflict, DbClient, DeleteParams, DeleteResult, Denied, Doc, InsertResult, NotFound,
+    OnConflict, PaginationResult, PatchParams, RestModel, Returning, UpdateResult, UpsertResult,
+};
+use serde_json::Value;
+use tracing::debug;
+
+/// `DbClient` backed by another service's rest_model HTTP API.
+///
+/// Each model lives at `{base_url}/{table_name}` and is accessed with the
+/// methods documented in the README, so `Guest::get(&client, ..)` works the
+/// same against a remote server as against a local database.
+#[derive(Debug, Clone)]
+pub struct HttpDb {
+    pub base_url: String,
+    pub client: Client,
+}
+
+impl HttpDb {
+    pub fn new(base_url: &str) -> Self {
+        Self::with_client(base_url, Client::new())
+    }
+
+    /// Uses a preconfigured client, e.g. with default auth headers or timeouts.
+    pub fn with_client(base_url: &str, client: Client) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+        }
+    }
+
+    fn request(&self, method: Method, table_name: &str, id: Option<&str>) -> RequestBuilder {
+        let url = match id {
+            Some(id) => format!("{}/{}/{}", self.base_url, table_name, encode(id)),
+            None => format!("{}/{}", self.base_url, table_name),
+        };
+        debug!("{} {}", method, url);
+        self.client.request(method, url)
+    }
+}
+
+/// Maps error statuses to the same errors the database backends return.
+async fn check(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let url = response.url().to_string();
+    let body = response.text().await.unwrap_or_default();
+    match status {
+        StatusCode::NOT_FOUND => Err(NotFound.into()),
+        StatusCode::FORBIDDEN => Err(Denied::new(body).into()),
+        StatusCode::CONFLICT => match serde_json::from_str::<Conflict>(&body) {
+            Ok(conflict) => Err(conflict.into()),
+            Err(_) => Err(anyhow!("{} failed: {} {}", url, status, body)),
+        },
+        _ => Err(anyhow!("{} failed: {} {}", url, status, body)),
+    }
+}
+
+/// `?returning=..`, left out when nothing extra is requested so older servers
+/// keep working.
+fn returning_query(returning: Returning) -> Vec<(&'static str, Returning)> {
+    match returning {
+        Returning::None => vec![],
+        _ => vec![("returning", returning)],
+    }
+}
+
+/// Percent-encodes a path segment.
+fn encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+impl<T: RestModel> DbClient<T> for HttpDb {
+    fn generate_id(&self) -> String {
+        ObjectId::new().to_hex()
+    }
+
+    /// Tables belong to the serving side, so there is nothing to create.
+    async fn init(&self, _db_name: &str, _table_name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn select_by_id(&self, _db_name: &str, table_name: &str, id: &str) -> Result<Doc<T>> {
+        let response = self
+            .request(Method::GET, table_name, Some(id))
+            .send()
+            .await?;
+        Ok(check(response).await?.json().await?)
+    }
+
+    async fn paginate(
+        &self,
+        _db_name: &str,
+        table_name: &str,
+        pagination_params: &PaginationParams,
+    ) -> Result<PaginationResult<T>> {
+        let response = self
+            .request(Method::GET, table_name, None)
+            .query(&pagination_params.to_query()?)
+            .send()
+            .await?;
+        let response = check(response).await?;
+        let headers = response.headers().clone();
+        let body: Value = response.json().await?;
+        // servers either send the items with pagination headers, or the whole result
+        if body.is_array() {
+            Ok(PaginationResult {
+                items: serde_json::from_value(body)?,
+                pagination: Pagination::from_headers(|name| {
+                    headers.get(name).and_then(|v| v.to_str().ok())
+                })?,
+            })
+        } else {
+            Ok(serde_json::from_value(body)?)
+        }
+    }
+
+    async fn upsert(
+        &self,
+        _db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        returning: Returning,
+    ) -> Result<UpsertResult<T>> {
+        let response = self
+            .request(Method::PUT, table_name, None)
+            .query(&returning_query(returning))
+            .json(items)
+            .send()
+            .await?;
+        Ok(check(response).await?.json().await?)
+    }
+
+    async fn insert(
+        &self,
+        _db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        on_conflict: OnConflict,
+    ) -> Result<InsertResult> {
+        let response = self
+            .request(Method::POST, table_name, None)
+            .query(&[("on_conflict", on_conflict)])
+            .json(items)
+            .send()
+            .await?;
+        Ok(check(response).await?.json().await?)
+    }
+
+    async fn update(
+        &self,
+        _db_name: &str,
+        table_name: &str,
+        params: &PatchParams,
+        returning: Returning,
+    ) -> Result<UpdateResult<T>> {
+        let response = self
+            .request(Method::PATCH, table_name, None)
+            .query(&returning_query(returning))
+            .json(params)
+            .send()
+            .await?;
+        Ok(check(response).await?.json().await?)
+    }
+
+    async fn delete(
+        &self,
+        _db_name: &str,
+        table_name: &str,
+        params: &DeleteParams,
+        returning: Returning,
+    ) -> Result<DeleteResult<T>> {
+        let response = self
+            .request(Method::DELETE, table_name, None)
+            .query(&returning_query(returning))
+            .json(params)
+            .send()
+            .await?;
+        Ok(check(response).await?.json().await?)
+    }
+}
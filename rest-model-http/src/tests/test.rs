@@ -0,0 +1,255 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use rest_model::{
+    method::{Delete, Get, GetMany, GetWithId, Init, Patch, Post, Put},
+    pagination::PaginationParams,
+    rest_model, Condition, Conflict, DeleteParams, DeleteResult, Doc, InsertResult, NotFound,
+    OnConflict, PatchParams, Returning, UpdateResult, UpsertResult,
+};
+use rest_model_http::HttpDb;
+use rest_model_postgres::Db;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+const DB_NAME: &str = "mydb.public";
+const TABLE_NAME: &str = "guests";
+
+#[rest_model(db(HttpDb, DB_NAME, TABLE_NAME), with(all))]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Guest {
+    pub name: String,
+    pub age: i32,
+}
+
+impl Init<Guest, Db> for Guest {}
+impl GetWithId<Guest, Db> for Guest {}
+impl Get<Guest, Db> for Guest {}
+impl Post<Guest, Db> for Guest {}
+impl Put<Guest, Db> for Guest {}
+impl Patch<Guest, Db> for Guest {}
+impl Delete<Guest, Db> for Guest {}
+
+type Error = (StatusCode, String);
+
+fn error(e: anyhow::Error) -> Error {
+    if e.downcast_ref::<NotFound>().is_some() {
+        (StatusCode::NOT_FOUND, e.to_string())
+    } else if let Some(conflict) = e.downcast_ref::<Conflict>() {
+        (
+            StatusCode::CONFLICT,
+            serde_json::to_string(conflict).unwrap(),
+        )
+    } else {
+        (StatusCode::BAD_REQUEST, e.to_string())
+    }
+}
+
+async fn get_guests(
+    State(db): State<Db>,
+    Query(query): Query<Vec<(String, String)>>,
+) -> Result<(HeaderMap, Json<Vec<Doc<Guest>>>), Error> {
+    let params = PaginationParams::from_query(query.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+        .map_err(error)?;
+    let result = <Guest as Get<Guest, Db>>::get(&db, &params)
+        .await
+        .map_err(error)?;
+    let mut headers = HeaderMap::new();
+    for (name, value) in result.pagination.to_headers() {
+        headers.insert(name, value.parse().unwrap());
+    }
+    Ok((headers, Json(result.items)))
+}
+
+async fn get_guest(
+    State(db): State<Db>,
+    Path(id): Path<String>,
+) -> Result<Json<Doc<Guest>>, Error> {
+    <Guest as GetWithId<Guest, Db>>::get_with_id(&db, &id)
+        .await
+        .map(Json)
+        .map_err(error)
+}
+
+#[derive(Deserialize)]
+struct PostQuery {
+    #[serde(default)]
+    on_conflict: OnConflict,
+}
+
+async fn post_guests(
+    State(db): State<Db>,
+    Query(query): Query<PostQuery>,
+    Json(items): Json<Vec<Doc<Guest>>>,
+) -> Result<Json<InsertResult>, Error> {
+    <Guest as Post<Guest, Db>>::post(&db, &items, query.on_conflict)
+        .await
+        .map(Json)
+        .map_err(error)
+}
+
+#[derive(Deserialize)]
+struct WriteQuery {
+    #[serde(default)]
+    returning: Returning,
+}
+
+async fn put_guests(
+    State(db): State<Db>,
+    Query(query): Query<WriteQuery>,
+    Json(items): Json<Vec<Doc<Guest>>>,
+) -> Result<Json<UpsertResult<Guest>>, Error> {
+    <Guest as Put<Guest, Db>>::put_returning(&db, &items, query.returning)
+        .await
+        .map(Json)
+        .map_err(error)
+}
+
+async fn patch_guests(
+    State(db): State<Db>,
+    Query(query): Query<WriteQuery>,
+    Json(params): Json<PatchParams>,
+) -> Result<Json<UpdateResult<Guest>>, Error> {
+    <Guest as Patch<Guest, Db>>::patch_returning(&db, &params, query.returning)
+        .await
+        .map(Json)
+        .map_err(error)
+}
+
+async fn delete_guests(
+    State(db): State<Db>,
+    Query(query): Query<WriteQuery>,
+    Json(params): Json<DeleteParams>,
+) -> Result<Json<DeleteResult<Guest>>, Error> {
+    <Guest as Delete<Guest, Db>>::delete_returning(&db, &params, query.returning)
+        .await
+        .map(Json)
+        .map_err(error)
+}
+
+/// Serves `Guest` from Postgres on a random local port and returns its base URL.
+async fn serve() -> String {
+    let uri = &std::env::var("DATABASE_URL").unwrap();
+    let db = Db::try_new(uri)
+        .await
+        .unwrap()
+        .with_schema("test_http")
+        .unwrap();
+    <Guest as Init<Guest, Db>>::init(&db).await.unwrap();
+    <Guest as Delete<Guest, Db>>::delete(
+        &db,
+        &DeleteParams {
+            filter: Condition::Gt("age".to_string(), json!(0)),
+        },
+    )
+    .await
+    .unwrap();
+    let app = Router::new()
+        .route(
+            "/guests",
+            get(get_guests)
+                .post(post_guests)
+                .put(put_guests)
+                .patch(patch_guests)
+                .delete(delete_guests),
+        )
+        .route("/guests/{id}", get(get_guest))
+        .with_state(db);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+#[test]
+fn http_db() {
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let client = HttpDb::new(&serve().await);
+        let tom = Doc::new(
+            &client,
+            Guest {
+                name: "Tom".to_string(),
+                age: 10,
+            },
+        );
+        let jerry = Doc::new(
+            &client,
+            Guest {
+                name: "Jerry".to_string(),
+                age: 9,
+            },
+        );
+        let result = Guest::put(&client, &[tom.clone(), jerry.clone()])
+            .await
+            .unwrap();
+        assert_eq!(result.created_count, 2);
+
+        let err = Guest::post(&client, std::slice::from_ref(&tom), OnConflict::Fail)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Conflict>().unwrap().ids,
+            vec![tom._id.clone()]
+        );
+        let result = Guest::post(&client, std::slice::from_ref(&tom), OnConflict::Skip)
+            .await
+            .unwrap();
+        assert_eq!(result.skipped_ids, vec![tom._id.clone()]);
+
+        let doc = Guest::get_with_id(&client, &tom._id).await.unwrap();
+        assert_eq!(doc.data.name, "Tom");
+        let err = Guest::get_with_id(&client, "000000000000000000000000")
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<NotFound>().is_some());
+
+        let docs = Guest::get_many(&client, &[&jerry._id, "000000000000000000000000", &tom._id])
+            .await
+            .unwrap();
+        assert_eq!(docs[0].as_ref().unwrap().data.name, "Jerry");
+        assert!(docs[1].is_none());
+        assert_eq!(docs[2].as_ref().unwrap().data.name, "Tom");
+
+        let page = Guest::get(
+            &client,
+            &PaginationParams {
+                limit: Some(1),
+                sort: Some("+name".to_string()),
+                filter: Some(Condition::Gt("age".to_string(), json!(5))),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(page.items[0].data.name, "Jerry");
+        assert_eq!(page.pagination.total_count, 2);
+        assert_eq!(page.pagination.total_pages, 2);
+
+        let result = Guest::patch_returning(
+            &client,
+            &PatchParams {
+                filter: Condition::Eq("name".to_string(), json!("Tom")),
+                patch: json!({ "age": 11 }),
+            },
+            Returning::Docs,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.updated_count, 1);
+        assert_eq!(result.ids.unwrap(), vec![tom._id.clone()]);
+        assert_eq!(result.docs.unwrap()[0].data.age, 11);
+
+        let result = Guest::delete(
+            &client,
+            &DeleteParams {
+                filter: Condition::Eq("name".to_string(), json!("Jerry")),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.deleted_count, 1);
+    });
+}
@@ -2,19 +2,114 @@ extern crate proc_macro;
 use core::panic;
 
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, DeriveInput, Ident};
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, punctuated::Punctuated, Data, DeriveInput, Fields, Ident, Token};
+
+mod schema;
+use schema::{json_string, serde_field, serde_rename_all, sort_type, type_schema};
 
 /// Procedural macro to create a new struct with optional fields and copied derives
 #[proc_macro_attribute]
 pub fn rest_model(args: TokenStream, item: TokenStream) -> TokenStream {
     // Parse the input token stream as a struct
-    let input = parse_macro_input!(item as DeriveInput);
+    let mut input = parse_macro_input!(item as DeriveInput);
+
+    // Collect `#[field(read(..), write(..))]` and strip it from the emitted struct
+    let mut field_permissions = vec![];
+    // (serde name, `SortType` variant) of every field that doesn't sort as text
+    let mut sort_types = vec![];
+    // (Rust ident, serde name) of every field, to resolve `ref(..)`
+    let mut field_names = vec![];
+    // Rust idents of the `Option` fields, which `set_null` may clear
+    let mut nullable = vec![];
+    let mut properties = vec![];
+    let mut required = vec![];
+    let rename_all = serde_rename_all(&input.attrs);
+    if let Data::Struct(data) = &mut input.data {
+        if let Fields::Named(fields) = &mut data.fields {
+            for field in fields.named.iter_mut() {
+                let serde_field = serde_field(field, rename_all.as_deref());
+                let name = serde_field.name;
+                field_names.push((field.ident.as_ref().unwrap().to_string(), name.clone()));
+                let (schema, optional) = type_schema(&field.ty);
+                if optional {
+                    nullable.push(field.ident.as_ref().unwrap().to_string());
+                }
+                // a flattened type's keys aren't known here, so they stay unconstrained
+                if !serde_field.skip && !serde_field.flatten {
+                    properties.push(format!("{}:{}", json_string(&name), schema));
+                    if !optional && !serde_field.default {
+                        required.push(json_string(&name));
+                    }
+                }
+                let mut read: Option<Vec<String>> = None;
+                let mut write: Option<Vec<String>> = None;
+                let mut sort = sort_type(&field.ty);
+                for attr in field.attrs.iter().filter(|a| a.path().is_ident("field")) {
+                    let result = attr.parse_nested_meta(|meta| {
+                        if meta.path.is_ident("sort") {
+                            // `sort(timestamp)` for e.g. a `String` holding RFC 3339 dates
+                            let content;
+                            syn::parenthesized!(content in meta.input);
+                            let ident: Ident = content.parse()?;
+                            sort = match ident.to_string().as_str() {
+                                "text" => "Text",
+                                "number" => "Number",
+                                "timestamp" => "Timestamp",
+                                _ => {
+                                    return Err(syn::Error::new(
+                                        ident.span(),
+                                        "expected text, number or timestamp",
+                                    ))
+                                }
+                            };
+                            return Ok(());
+                        }
+                        let roles = if meta.path.is_ident("read") {
+                            read.get_or_insert_with(Vec::new)
+                        } else if meta.path.is_ident("write") {
+                            write.get_or_insert_with(Vec::new)
+                        } else {
+                            return Err(meta.error("unsupported rest_model field property"));
+                        };
+                        // `read()` is allowed and means no role at all
+                        let content;
+                        syn::parenthesized!(content in meta.input);
+                        let idents = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+                        roles.extend(idents.iter().map(|i| i.to_string()));
+                        Ok(())
+                    });
+                    if let Err(e) = result {
+                        return e.to_compile_error().into();
+                    }
+                }
+                field.attrs.retain(|a| !a.path().is_ident("field"));
+                if sort != "Text" {
+                    sort_types.push((name.clone(), sort));
+                }
+                if read.is_some() || write.is_some() {
+                    field_permissions.push((name, read, write));
+                }
+            }
+        }
+    }
+
+    let data_schema = format!(
+        r#"{{"type":"object","properties":{{{}}},"required":[{}]}}"#,
+        properties.join(","),
+        required.join(",")
+    );
+
     let original = input.clone();
     let struct_name = input.ident;
 
     let mut get = false;
     let mut get_with_id = false;
+    let mut get_many = false;
+    let mut aggregate = false;
+    let mut distinct = false;
+    let mut post = false;
     let mut put = false;
     let mut patch = false;
     let mut delete = false;
@@ -23,6 +118,10 @@ pub fn rest_model(args: TokenStream, item: TokenStream) -> TokenStream {
     let mut db: Option<Ident> = None;
     let mut db_name: Option<Ident> = None;
     let mut table_name: Option<Ident> = None;
+    let mut references: Vec<(Ident, syn::Path, Ident)> = vec![];
+    let mut referrers: Vec<syn::Path> = vec![];
+    // serde names of the searched fields, and the text search configuration
+    let mut search: Option<(Vec<String>, String)> = None;
     let parser = syn::meta::parser(|meta| {
         if meta.path.is_ident("db") {
             let mut i = 0;
@@ -40,7 +139,7 @@ pub fn rest_model(args: TokenStream, item: TokenStream) -> TokenStream {
                 i += 1;
                 Ok(())
             })?;
-            if i != 1 || i != 3 {
+            if i != 1 && i != 3 {
                 return Err(meta.error("rest_model db only support 1 or 3 params"));
             }
             Ok(())
@@ -52,6 +151,18 @@ pub fn rest_model(args: TokenStream, item: TokenStream) -> TokenStream {
                 } else if meta.path.is_ident("get_with_id") {
                     get_with_id = true;
                     Ok(())
+                } else if meta.path.is_ident("get_many") {
+                    get_many = true;
+                    Ok(())
+                } else if meta.path.is_ident("aggregate") {
+                    aggregate = true;
+                    Ok(())
+                } else if meta.path.is_ident("distinct") {
+                    distinct = true;
+                    Ok(())
+                } else if meta.path.is_ident("post") {
+                    post = true;
+                    Ok(())
                 } else if meta.path.is_ident("put") {
                     put = true;
                     Ok(())
@@ -64,6 +175,10 @@ pub fn rest_model(args: TokenStream, item: TokenStream) -> TokenStream {
                 } else if meta.path.is_ident("all") {
                     get = true;
                     get_with_id = true;
+                    get_many = true;
+                    aggregate = true;
+                    distinct = true;
+                    post = true;
                     put = true;
                     patch = true;
                     delete = true;
@@ -72,10 +187,90 @@ pub fn rest_model(args: TokenStream, item: TokenStream) -> TokenStream {
                     Err(meta.error("unsupported rest_model with property"))
                 }
             })
+        } else if meta.path.is_ident("ref") {
+            // `ref(customer_id -> Customer, product_id -> Product(cascade), ..)`
+            let content;
+            syn::parenthesized!(content in meta.input);
+            while !content.is_empty() {
+                let field: Ident = content.parse()?;
+                content.parse::<Token![->]>()?;
+                let model: syn::Path = content.parse()?;
+                if !field_names.iter().any(|(ident, _)| field == ident) {
+                    return Err(syn::Error::new(field.span(), "no such field"));
+                }
+                let on_delete = if content.peek(syn::token::Paren) {
+                    let action;
+                    syn::parenthesized!(action in content);
+                    let action: Ident = action.parse()?;
+                    let variant = match action.to_string().as_str() {
+                        "restrict" => "Restrict",
+                        "cascade" => "Cascade",
+                        // a non-`Option` field holding `null` would no longer deserialize
+                        "set_null" if !nullable.iter().any(|ident| field == ident) => {
+                            return Err(syn::Error::new(
+                                action.span(),
+                                "set_null needs an Option field",
+                            ))
+                        }
+                        "set_null" => "SetNull",
+                        _ => {
+                            return Err(syn::Error::new(
+                                action.span(),
+                                "expected restrict, cascade or set_null",
+                            ))
+                        }
+                    };
+                    Ident::new(variant, action.span())
+                } else {
+                    Ident::new("Restrict", field.span())
+                };
+                references.push((field, model, on_delete));
+                if content.is_empty() {
+                    break;
+                }
+                content.parse::<Token![,]>()?;
+            }
+            Ok(())
+        } else if meta.path.is_ident("search") {
+            // `search(name, description, tags, language = "english")`
+            let content;
+            syn::parenthesized!(content in meta.input);
+            let (fields, language) = search.get_or_insert_with(|| (vec![], "english".to_string()));
+            while !content.is_empty() {
+                let ident: Ident = content.parse()?;
+                if content.peek(Token![=]) {
+                    if ident != "language" {
+                        return Err(syn::Error::new(ident.span(), "expected language"));
+                    }
+                    content.parse::<Token![=]>()?;
+                    *language = content.parse::<syn::LitStr>()?.value();
+                } else {
+                    let Some((_, name)) = field_names.iter().find(|(field, _)| ident == field)
+                    else {
+                        return Err(syn::Error::new(ident.span(), "no such field"));
+                    };
+                    fields.push(name.clone());
+                }
+                if content.is_empty() {
+                    break;
+                }
+                content.parse::<Token![,]>()?;
+            }
+            if fields.is_empty() {
+                return Err(meta.error("search needs at least one field"));
+            }
+            Ok(())
+        } else if meta.path.is_ident("referenced_by") {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            referrers.extend(Punctuated::<syn::Path, Token![,]>::parse_terminated(
+                &content,
+            )?);
+            Ok(())
         } else {
             Err(meta.error(format!(
                 "unsupported rest_model property `{}`",
-                meta.path.get_ident().unwrap().to_string()
+                meta.path.get_ident().unwrap()
             )))
         }
     });
@@ -91,6 +286,99 @@ pub fn rest_model(args: TokenStream, item: TokenStream) -> TokenStream {
         impl rest_model::method::Init<#struct_name, #db> for #struct_name {}
     };
 
+    let roles_tokens = |roles: &Option<Vec<String>>| match roles {
+        Some(roles) => quote! { Some(&[#(#roles),*]) },
+        None => quote! { None },
+    };
+    let field_permissions = field_permissions
+        .iter()
+        .map(|(field, read, write)| {
+            let read = roles_tokens(read);
+            let write = roles_tokens(write);
+            quote! {
+                rest_model::FieldPermission {
+                    field: #field,
+                    read: #read,
+                    write: #write,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let sort_types = sort_types
+        .iter()
+        .map(|(field, sort_type)| {
+            let sort_type = Ident::new(sort_type, proc_macro2::Span::call_site());
+            quote! {
+                rest_model::FieldSort {
+                    field: #field,
+                    sort_type: rest_model::SortType::#sort_type,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // `ref(field -> Model)` is only enforced if `Model` lists this one in
+    // `referenced_by(..)`, so a missing back-reference is a compile error
+    let back_references = references
+        .iter()
+        .map(|(_, model, _)| {
+            quote_spanned! {model.span()=>
+                const _: fn() = rest_model::assert_referenced_by::<#model, #struct_name>;
+            }
+        })
+        .collect::<Vec<_>>();
+    let references = references
+        .iter()
+        .map(|(field, model, on_delete)| {
+            let field = &field_names
+                .iter()
+                .find(|(ident, _)| field == ident)
+                .unwrap()
+                .1;
+            quote! {
+                rest_model::Reference {
+                    field: #field,
+                    db_name: <#model as rest_model::RestModel>::get_db_name,
+                    table_name: <#model as rest_model::RestModel>::get_table_name,
+                    on_delete: rest_model::OnDelete::#on_delete,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let referrer_impls = referrers
+        .iter()
+        .map(|model| {
+            quote! {
+                impl rest_model::ReferencedBy<#model> for #struct_name {}
+            }
+        })
+        .collect::<Vec<_>>();
+    let referrers = referrers
+        .iter()
+        .map(|model| {
+            quote! {
+                rest_model::Referrer {
+                    db_name: <#model as rest_model::RestModel>::get_db_name,
+                    table_name: <#model as rest_model::RestModel>::get_table_name,
+                    references: <#model as rest_model::RestModel>::get_references,
+                    referrers: <#model as rest_model::RestModel>::get_referrers,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let has_search = search.is_some();
+    let search = match search {
+        Some((fields, language)) => quote! {
+            Some(rest_model::SearchConfig {
+                fields: &[#(#fields),*],
+                language: #language,
+            })
+        },
+        None => quote! { None },
+    };
+
     if db_name.is_some() && table_name.is_some() {
         methods.extend(quote! {
             impl rest_model::RestModel for #struct_name {
@@ -100,20 +388,91 @@ pub fn rest_model(args: TokenStream, item: TokenStream) -> TokenStream {
                 fn get_table_name() -> &'static str {
                     #table_name
                 }
+                fn get_field_permissions() -> &'static [rest_model::FieldPermission] {
+                    &[#(#field_permissions),*]
+                }
+                fn get_references() -> &'static [rest_model::Reference] {
+                    &[#(#references),*]
+                }
+                fn get_referrers() -> &'static [rest_model::Referrer] {
+                    &[#(#referrers),*]
+                }
+                fn get_sort_types() -> &'static [rest_model::FieldSort] {
+                    &[#(#sort_types),*]
+                }
+                fn get_search() -> Option<rest_model::SearchConfig> {
+                    #search
+                }
             }
+            #(#back_references)*
+            #(#referrer_impls)*
         });
+    } else if !field_permissions.is_empty() {
+        panic!("Field permissions require db(Db, db_name, table_name)");
+    } else if !references.is_empty() || !referrers.is_empty() {
+        panic!("References require db(Db, db_name, table_name)");
+    } else if has_search {
+        panic!("Search requires db(Db, db_name, table_name)");
     }
 
+    let mut operations = vec![];
+    for (enabled, operation) in [
+        (get, quote! { Get }),
+        (get_with_id, quote! { GetWithId }),
+        (post, quote! { Post }),
+        (put, quote! { Put }),
+        (patch, quote! { Patch }),
+        (delete, quote! { Delete }),
+    ] {
+        if enabled {
+            operations.push(quote! { rest_model::Operation::#operation });
+        }
+    }
+    let model_name = struct_name.to_string();
+    methods.extend(quote! {
+        impl rest_model::ModelSchema for #struct_name {
+            fn get_model_name() -> &'static str {
+                #model_name
+            }
+            fn get_operations() -> &'static [rest_model::Operation] {
+                &[#(#operations),*]
+            }
+            fn get_data_schema() -> rest_model::serde_json::Value {
+                rest_model::serde_json::from_str(#data_schema).unwrap()
+            }
+        }
+    });
+
     if get_with_id {
         methods.extend(quote! {
             impl rest_model::method::GetWithId<#struct_name, #db> for #struct_name {}
         });
     }
+    if get_many {
+        methods.extend(quote! {
+            impl rest_model::method::GetMany<#struct_name, #db> for #struct_name {}
+        });
+    }
     if get {
         methods.extend(quote! {
             impl rest_model::method::Get<#struct_name, #db> for #struct_name {}
         });
     }
+    if aggregate {
+        methods.extend(quote! {
+            impl rest_model::method::Aggregate<#struct_name, #db> for #struct_name {}
+        });
+    }
+    if distinct {
+        methods.extend(quote! {
+            impl rest_model::method::Distinct<#struct_name, #db> for #struct_name {}
+        });
+    }
+    if post {
+        methods.extend(quote! {
+            impl rest_model::method::Post<#struct_name, #db> for #struct_name {}
+        });
+    }
     if put {
         methods.extend(quote! {
             impl rest_model::method::Put<#struct_name, #db> for #struct_name {}
@@ -0,0 +1,227 @@
+use syn::{Attribute, Field, GenericArgument, LitStr, PathArguments, Type};
+
+/// How serde names and treats a struct field.
+pub struct SerdeField {
+    pub name: String,
+    pub skip: bool,
+    pub default: bool,
+    /// Spreads its own fields into the parent object, so it has no key of its own.
+    pub flatten: bool,
+}
+
+/// Reads the container's `#[serde(rename_all = "..")]`, or its `serialize` rule.
+pub fn serde_rename_all(attrs: &[Attribute]) -> Option<String> {
+    let mut rename_all = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                if meta.input.peek(syn::Token![=]) {
+                    let rule: LitStr = meta.value()?.parse()?;
+                    rename_all = Some(rule.value());
+                } else {
+                    meta.parse_nested_meta(|inner| {
+                        let rule: LitStr = inner.value()?.parse()?;
+                        if inner.path.is_ident("serialize") {
+                            rename_all = Some(rule.value());
+                        }
+                        Ok(())
+                    })?;
+                }
+            } else if meta.input.peek(syn::Token![=]) {
+                let _: syn::Expr = meta.value()?.parse()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                meta.parse_nested_meta(|_| Ok(()))?;
+            }
+            Ok(())
+        });
+    }
+    rename_all
+}
+
+/// Applies a serde `rename_all` rule to a snake_case field name, as serde does.
+fn rename_field(name: &str, rule: &str) -> String {
+    let pascal = || {
+        let mut pascal = String::new();
+        let mut capitalize = true;
+        for c in name.chars() {
+            if c == '_' {
+                capitalize = true;
+            } else if capitalize {
+                pascal.push(c.to_ascii_uppercase());
+                capitalize = false;
+            } else {
+                pascal.push(c);
+            }
+        }
+        pascal
+    };
+    match rule {
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => name.to_ascii_uppercase(),
+        "PascalCase" => pascal(),
+        "camelCase" => {
+            let pascal = pascal();
+            let mut chars = pascal.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_ascii_lowercase().to_string() + chars.as_str()
+            })
+        }
+        "kebab-case" => name.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => name.to_ascii_uppercase().replace('_', "-"),
+        _ => name.to_string(),
+    }
+}
+
+/// Reads `#[serde(rename = "..", skip, default, flatten)]` so generated metadata matches the JSON.
+/// `rename_all` is the container's rule, which an explicit `rename` overrides.
+pub fn serde_field(field: &Field, rename_all: Option<&str>) -> SerdeField {
+    let ident = field.ident.as_ref().unwrap().to_string();
+    let ident = ident.trim_start_matches("r#");
+    let mut serde_field = SerdeField {
+        name: rename_all.map_or_else(|| ident.to_string(), |rule| rename_field(ident, rule)),
+        skip: false,
+        default: false,
+        flatten: false,
+    };
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                let name: LitStr = meta.value()?.parse()?;
+                serde_field.name = name.value();
+            } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                serde_field.skip = true;
+            } else if meta.path.is_ident("flatten") {
+                serde_field.flatten = true;
+            } else if meta.path.is_ident("default") {
+                serde_field.default = true;
+                if meta.input.peek(syn::Token![=]) {
+                    let _: LitStr = meta.value()?.parse()?;
+                }
+            } else if meta.input.peek(syn::Token![=]) {
+                let _: syn::Expr = meta.value()?.parse()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                meta.parse_nested_meta(|_| Ok(()))?;
+            }
+            Ok(())
+        });
+    }
+    serde_field
+}
+
+/// JSON Schema (as JSON text) for a Rust type, by syntax alone so unknown
+/// types never break compilation; they map to `{}` (any value).
+/// The flag is true for `Option<_>`, whose field may be omitted.
+pub fn type_schema(ty: &Type) -> (String, bool) {
+    match ty {
+        Type::Reference(r) => type_schema(&r.elem),
+        Type::Paren(p) => type_schema(&p.elem),
+        Type::Group(g) => type_schema(&g.elem),
+        Type::Array(a) => (array(&type_schema(&a.elem).0), false),
+        Type::Slice(s) => (array(&type_schema(&s.elem).0), false),
+        Type::Path(p) => {
+            let Some(segment) = p.path.segments.last() else {
+                return ("{}".to_string(), false);
+            };
+            let args = match &segment.arguments {
+                PathArguments::AngleBracketed(args) => args
+                    .args
+                    .iter()
+                    .filter_map(|a| match a {
+                        GenericArgument::Type(t) => Some(t),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>(),
+                _ => vec![],
+            };
+            let inner = |i: usize| {
+                args.get(i)
+                    .map(|t| type_schema(t).0)
+                    .unwrap_or_else(|| "{}".to_string())
+            };
+            let schema = match segment.ident.to_string().as_str() {
+                "String" | "str" | "char" => r#"{"type":"string"}"#.to_string(),
+                "i8" | "i16" | "i32" | "i64" | "i128" | "isize" => {
+                    r#"{"type":"integer"}"#.to_string()
+                }
+                "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => {
+                    r#"{"type":"integer","minimum":0}"#.to_string()
+                }
+                "f32" | "f64" => r#"{"type":"number"}"#.to_string(),
+                "bool" => r#"{"type":"boolean"}"#.to_string(),
+                "Option" => {
+                    return (
+                        format!(r#"{{"anyOf":[{},{{"type":"null"}}]}}"#, inner(0)),
+                        true,
+                    )
+                }
+                "Box" | "Rc" | "Arc" | "Cow" => match args.first() {
+                    Some(t) => return type_schema(t),
+                    None => "{}".to_string(),
+                },
+                "Vec" | "VecDeque" | "LinkedList" => array(&inner(0)),
+                "HashSet" | "BTreeSet" => format!(
+                    r#"{{"type":"array","items":{},"uniqueItems":true}}"#,
+                    inner(0)
+                ),
+                "HashMap" | "BTreeMap" => {
+                    format!(r#"{{"type":"object","additionalProperties":{}}}"#, inner(1))
+                }
+                _ => "{}".to_string(),
+            };
+            (schema, false)
+        }
+        _ => ("{}".to_string(), false),
+    }
+}
+
+/// The `SortType` variant for a Rust type, by syntax alone like `type_schema`;
+/// date and time types are recognised by name.
+pub fn sort_type(ty: &Type) -> &'static str {
+    match ty {
+        Type::Reference(r) => sort_type(&r.elem),
+        Type::Paren(p) => sort_type(&p.elem),
+        Type::Group(g) => sort_type(&g.elem),
+        Type::Path(p) => {
+            let Some(segment) = p.path.segments.last() else {
+                return "Text";
+            };
+            match segment.ident.to_string().as_str() {
+                "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
+                | "u128" | "usize" | "f32" | "f64" => "Number",
+                "DateTime" | "NaiveDateTime" | "NaiveDate" | "OffsetDateTime"
+                | "PrimitiveDateTime" | "Date" | "Timestamp" | "Zoned" => "Timestamp",
+                "Option" | "Box" | "Rc" | "Arc" | "Cow" => match &segment.arguments {
+                    PathArguments::AngleBracketed(args) => args
+                        .args
+                        .iter()
+                        .find_map(|a| match a {
+                            GenericArgument::Type(t) => Some(sort_type(t)),
+                            _ => None,
+                        })
+                        .unwrap_or("Text"),
+                    _ => "Text",
+                },
+                _ => "Text",
+            }
+        }
+        _ => "Text",
+    }
+}
+
+fn array(items: &str) -> String {
+    format!(r#"{{"type":"array","items":{}}}"#, items)
+}
+
+/// A JSON string literal.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,208 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::{bail, Result};
+use bb8_postgres::{
+    bb8::{Builder, ManageConnection, Pool},
+    PostgresConnectionManager,
+};
+use rest_model::Namespace;
+use rustls::{
+    crypto::ring,
+    pki_types::{pem::PemObject, CertificateDer},
+    ClientConfig, RootCertStore,
+};
+use tokio_postgres::{config::SslMode, NoTls};
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use crate::{replica::REPLICA_CONNECTION_TIMEOUT, Db, PgPool, Replicas};
+
+/// Configures the pools of a `Db`:
+///
+/// ```ignore
+/// let db = Db::builder("postgres://app@db.example.com/app")
+///     .max_size(20)
+///     .statement_timeout(Duration::from_secs(5))
+///     .application_name("orders-api")
+///     .tls_ca_pem(&std::fs::read("ca.pem")?)?
+///     .replica("postgres://app@replica.example.com/app")
+///     .build()
+///     .await?;
+/// ```
+///
+/// Everything but the URIs applies to the primary and every replica alike.
+#[derive(Debug, Clone)]
+pub struct DbBuilder {
+    uri: String,
+    replica_uris: Vec<String>,
+    max_size: u32,
+    min_idle: Option<u32>,
+    connection_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    statement_timeout: Option<Duration>,
+    application_name: Option<String>,
+    tls: Option<Arc<ClientConfig>>,
+}
+
+impl Db {
+    pub fn builder(postgres_uri: &str) -> DbBuilder {
+        DbBuilder {
+            uri: postgres_uri.to_string(),
+            replica_uris: vec![],
+            max_size: 10,
+            min_idle: None,
+            connection_timeout: None,
+            idle_timeout: None,
+            statement_timeout: None,
+            application_name: None,
+            tls: None,
+        }
+    }
+
+    /// Wraps a pool configured elsewhere, plain or TLS; it becomes the
+    /// primary, with no replicas.
+    pub fn from_pool(pool: impl Into<PgPool>) -> Self {
+        Self {
+            pool: pool.into(),
+            namespace: Namespace::default(),
+            replicas: Arc::new(Replicas::default()),
+            read_from_primary: false,
+        }
+    }
+}
+
+impl DbBuilder {
+    /// Adds a read replica; see `Db::try_new_with_replicas`.
+    pub fn replica(mut self, postgres_uri: &str) -> Self {
+        self.replica_uris.push(postgres_uri.to_string());
+        self
+    }
+
+    /// Connections per pool, 10 by default.
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Idle connections each pool keeps open, none by default.
+    pub fn min_idle(mut self, min_idle: u32) -> Self {
+        self.min_idle = Some(min_idle);
+        self
+    }
+
+    /// How long a checkout waits for a connection before failing; 30s by
+    /// default, 2s for replicas so reads fall back quickly.
+    pub fn connection_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_timeout = Some(timeout);
+        self
+    }
+
+    /// How long a connection may sit idle before it is closed; 10 minutes by default.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Server-side `statement_timeout` of every connection, in whole milliseconds.
+    pub fn statement_timeout(mut self, timeout: Duration) -> Self {
+        self.statement_timeout = Some(timeout);
+        self
+    }
+
+    /// Shown in `pg_stat_activity` and the server logs.
+    pub fn application_name(mut self, application_name: &str) -> Self {
+        self.application_name = Some(application_name.to_string());
+        self
+    }
+
+    /// Connects over TLS with `config`. The URI's default `sslmode=prefer`
+    /// becomes `require`, so a server without TLS is an error rather than a
+    /// silent downgrade.
+    pub fn tls(mut self, config: ClientConfig) -> Self {
+        self.tls = Some(Arc::new(config));
+        self
+    }
+
+    /// Connects over TLS, trusting only the CA certificates in `pem`, e.g.
+    /// a managed Postgres provider's bundle.
+    pub fn tls_ca_pem(self, pem: &[u8]) -> Result<Self> {
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(pem) {
+            roots.add(cert?)?;
+        }
+        if roots.is_empty() {
+            bail!("no certificate found in the CA PEM");
+        }
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(self.tls(config))
+    }
+
+    /// Creates the pools; only connects now if `min_idle` is set.
+    pub async fn build(self) -> Result<Db> {
+        let mut db = Db::from_pool(self.pool(&self.uri, self.connection_timeout).await?);
+        if !self.replica_uris.is_empty() {
+            let mut pools = vec![];
+            for uri in &self.replica_uris {
+                let timeout = self.connection_timeout.or(Some(REPLICA_CONNECTION_TIMEOUT));
+                pools.push(self.pool(uri, timeout).await?);
+            }
+            db.replicas = Arc::new(Replicas::new(pools));
+            Replicas::spawn_health_check(&db.replicas);
+        }
+        Ok(db)
+    }
+
+    async fn pool(&self, uri: &str, connection_timeout: Option<Duration>) -> Result<PgPool> {
+        let mut config = tokio_postgres::Config::from_str(uri)?;
+        if let Some(application_name) = &self.application_name {
+            config.application_name(application_name);
+        }
+        if let Some(timeout) = self.statement_timeout {
+            let option = format!("-c statement_timeout={}", timeout.as_millis());
+            let options = match config.get_options() {
+                Some(options) => format!("{} {}", options, option),
+                None => option,
+            };
+            config.options(&options);
+        }
+        Ok(match &self.tls {
+            Some(tls) => {
+                if config.get_ssl_mode() == SslMode::Prefer {
+                    config.ssl_mode(SslMode::Require);
+                }
+                let tls = MakeRustlsConnect::new(ClientConfig::clone(tls));
+                self.pool_builder(connection_timeout)
+                    .build(PostgresConnectionManager::new(config, tls))
+                    .await?
+                    .into()
+            }
+            None => {
+                if !matches!(config.get_ssl_mode(), SslMode::Disable | SslMode::Prefer) {
+                    bail!("sslmode={:?} needs DbBuilder::tls", config.get_ssl_mode());
+                }
+                self.pool_builder(connection_timeout)
+                    .build(PostgresConnectionManager::new(config, NoTls))
+                    .await?
+                    .into()
+            }
+        })
+    }
+
+    fn pool_builder<M: ManageConnection>(
+        &self,
+        connection_timeout: Option<Duration>,
+    ) -> Builder<M> {
+        let mut builder = Pool::builder()
+            .max_size(self.max_size)
+            .min_idle(self.min_idle);
+        if let Some(timeout) = connection_timeout {
+            builder = builder.connection_timeout(timeout);
+        }
+        if let Some(timeout) = self.idle_timeout {
+            builder = builder.idle_timeout(Some(timeout));
+        }
+        builder
+    }
+}
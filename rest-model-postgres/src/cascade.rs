@@ -0,0 +1,109 @@
+use std::{collections::HashMap, future::Future, pin::Pin};
+
+use anyhow::Result;
+use rest_model::{Namespace, OnDelete, Referenced, Referrer};
+use tokio_postgres::Transaction;
+use tracing::debug;
+
+use crate::table_ref;
+
+/// Counts by table for `DeleteResult::cascaded` and `DeleteResult::nullified`,
+/// keyed by the models' own table names, before any `Namespace` prefix.
+#[derive(Default)]
+pub struct Cascade {
+    pub cascaded: HashMap<String, u32>,
+    pub nullified: HashMap<String, u32>,
+}
+
+/// Applies the `on_delete` of every reference into the model stored at
+/// `target` (its compile-time `db_name`/`table_name`) to `ids`, which the
+/// caller has locked, following cascades through the deleted documents' own
+/// referrers.
+///
+/// Each referrer table is locked against writes until the transaction ends,
+/// so no reference to `ids` can commit after it was checked. Referrer tables
+/// not created in `namespace` hold no references and are skipped.
+pub fn on_delete<'a>(
+    tx: &'a Transaction<'a>,
+    namespace: &'a Namespace,
+    target: (&'static str, &'static str),
+    referrers: &'static [Referrer],
+    ids: Vec<String>,
+    cascade: &'a mut Cascade,
+) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+    Box::pin(async move {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        for referrer in referrers {
+            let references = (referrer.references)()
+                .iter()
+                .filter(|r| ((r.db_name)(), (r.table_name)()) == target);
+            for reference in references {
+                let model_table = (referrer.table_name)();
+                let (db_name, table_name) = namespace.resolve((referrer.db_name)(), model_table)?;
+                let table = table_ref(&db_name, &table_name)?;
+                let exists = tx
+                    .query_one("SELECT to_regclass($1) IS NOT NULL", &[&table])
+                    .await?;
+                if !exists.get::<_, bool>(0) {
+                    continue;
+                }
+                let sql = format!("LOCK TABLE {} IN SHARE ROW EXCLUSIVE MODE", table);
+                debug!("{}", sql);
+                tx.batch_execute(&sql).await?;
+                let matches = "data->>$1::TEXT = ANY($2)";
+                match reference.on_delete {
+                    OnDelete::Restrict => {
+                        let sql = format!("SELECT COUNT(*) FROM {} WHERE {}", table, matches);
+                        debug!("{}", sql);
+                        let row = tx.query_one(&sql, &[&reference.field, &ids]).await?;
+                        let count: i64 = row.get(0);
+                        if count > 0 {
+                            return Err(Referenced {
+                                table: model_table.to_string(),
+                                field: reference.field.to_string(),
+                                count: count as u32,
+                            }
+                            .into());
+                        }
+                    }
+                    OnDelete::Cascade => {
+                        let sql = format!("DELETE FROM {} WHERE {} RETURNING _id", table, matches);
+                        debug!("{}", sql);
+                        let rows = tx.query(&sql, &[&reference.field, &ids]).await?;
+                        *cascade.cascaded.entry(model_table.to_string()).or_default() +=
+                            rows.len() as u32;
+                        let deleted = rows.iter().map(|row| row.get(0)).collect();
+                        let target = ((referrer.db_name)(), (referrer.table_name)());
+                        on_delete(
+                            tx,
+                            namespace,
+                            target,
+                            (referrer.referrers)(),
+                            deleted,
+                            cascade,
+                        )
+                        .await?;
+                    }
+                    OnDelete::SetNull => {
+                        let sql = format!(
+                            "UPDATE {} SET
+                              data = jsonb_set(data, ARRAY[$1::TEXT], 'null'),
+                              _updated_at = (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT
+                            WHERE {}",
+                            table, matches
+                        );
+                        debug!("{}", sql);
+                        let updated = tx.execute(&sql, &[&reference.field, &ids]).await?;
+                        *cascade
+                            .nullified
+                            .entry(model_table.to_string())
+                            .or_default() += updated as u32;
+                    }
+                }
+            }
+        }
+        Ok(())
+    })
+}
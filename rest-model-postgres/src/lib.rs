@@ -1,29 +1,98 @@
-use anyhow::{bail, Result};
-use bb8_postgres::{bb8::Pool, tokio_postgres::NoTls, PostgresConnectionManager};
-use oid::ObjectId;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail, Result};
 use rest_model::{
-    pagination::{Pagination, PaginationParams},
-    DbClient, DeleteParams, DeleteResult, Doc, PaginationResult, PatchParams, RestModel,
-    UpdateResult, UpsertResult,
+    oid::ObjectId,
+    pagination::{expand_fields, Pagination, PaginationParams},
+    retry::Transient,
+    AggregateParams, Condition, Conflict, DbClient, DeleteParams, DeleteResult, Denied, Doc, Facet,
+    InsertResult, Missing, Namespace, NotFound, OnConflict, PaginationResult, PatchParams,
+    ReplaceResult, RestModel, Returning, SearchConfig, UpdateResult, UpsertResult, WriteGuard,
 };
 use serde_json::Value;
-use tokio_postgres::types::ToSql;
+use tokio_postgres::{
+    binary_copy::BinaryCopyInWriter,
+    types::{ToSql, Type},
+    Row,
+};
 
+mod builder;
+pub use builder::*;
+mod cascade;
+use cascade::{on_delete, Cascade};
 mod query;
 pub use query::*;
+mod pool;
+pub use pool::{PgConnection, PgPool};
+mod replica;
+pub use replica::{Replica, Replicas};
+mod schema;
+pub use schema::*;
+mod search;
+use search::SearchSql;
+mod stream;
+mod transient;
 use tracing::debug;
-mod oid;
 
 #[derive(Debug, Clone)]
 pub struct Db {
-    pub pool: Pool<PostgresConnectionManager<NoTls>>,
+    /// The primary, which takes every write.
+    pool: PgPool,
+    pub namespace: Namespace,
+    /// Where reads go when there are any; see `try_new_with_replicas`.
+    pub replicas: Arc<Replicas>,
+    read_from_primary: bool,
 }
 
 impl Db {
+    /// Shorthand for `Db::builder(postgres_uri).build()`.
     pub async fn try_new(postgres_uri: &str) -> Result<Self> {
-        let manager = PostgresConnectionManager::new_from_stringlike(postgres_uri, NoTls)?;
-        let pool = Pool::builder().max_size(10).build(manager).await?;
-        Ok(Self { pool })
+        Self::builder(postgres_uri).build().await
+    }
+
+    /// Like `try_new`, spreading `select_by_id`, `select_by_ids`, `paginate`,
+    /// `expand`, `aggregate`, `distinct` and `stream` round-robin over
+    /// `replica_uris`. Replicas failing a checkout or the periodic health
+    /// check are skipped until they recover; with none left, reads go to the
+    /// primary.
+    pub async fn try_new_with_replicas(postgres_uri: &str, replica_uris: &[&str]) -> Result<Self> {
+        replica_uris
+            .iter()
+            .fold(Self::builder(postgres_uri), |builder, uri| {
+                builder.replica(uri)
+            })
+            .build()
+            .await
+    }
+
+    /// The primary's pool, which takes every write.
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    /// Returns a client sharing this pool that resolves every model into `namespace`.
+    pub fn with_namespace(&self, namespace: Namespace) -> Self {
+        Self {
+            namespace,
+            ..self.clone()
+        }
+    }
+
+    /// Shorthand for `with_namespace(Namespace::with_schema(schema)?)`, e.g. one schema per tenant.
+    pub fn with_schema(&self, schema: &str) -> Result<Self> {
+        Ok(self.with_namespace(Namespace::with_schema(schema)?))
+    }
+
+    /// Returns a client sharing these pools that reads from the primary too,
+    /// for reads that must see a write just made despite replication lag.
+    pub fn primary(&self) -> Self {
+        Self {
+            read_from_primary: true,
+            ..self.clone()
+        }
     }
 }
 
@@ -32,40 +101,79 @@ impl<T: RestModel> DbClient<T> for Db {
         ObjectId::new().to_hex()
     }
 
+    fn resolve(&self, db_name: &str, table_name: &str) -> Result<(String, String)> {
+        self.namespace.resolve(db_name, table_name)
+    }
+
+    fn transient(&self, error: &anyhow::Error) -> Option<Transient> {
+        transient::transient(error)
+    }
+
+    fn data_error(&self, error: &anyhow::Error) -> bool {
+        transient::data_error(error)
+    }
+
     async fn init(
         &self,
         db_name: &str,
         table_name: &str,
     ) -> std::result::Result<(), anyhow::Error> {
+        // `db_name` is `[database.]schema`; the schema may be a tenant's that doesn't exist yet
+        let schema = db_name.rsplit('.').next().unwrap_or(db_name);
         let sql = format!(
-            "CREATE TABLE IF NOT EXISTS {}.{} (
+            "CREATE SCHEMA IF NOT EXISTS {};
+            CREATE TABLE IF NOT EXISTS {} (
                 _id VARCHAR(24) PRIMARY KEY,
                 data JSONB NOT NULL,
                 _created_at BIGINT DEFAULT (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT,
                 _updated_at BIGINT DEFAULT (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT
             );",
-            db_name, table_name
+            quote_ident(schema)?,
+            table_ref(db_name, table_name)?
         );
         self.pool
             .get()
             .await?
-            .execute(&sql, &[])
+            .batch_execute(&sql)
+            .await
+            .map_err(anyhow::Error::from)
+    }
+
+    async fn install_schema(&self, db_name: &str, table_name: &str, schema: &Value) -> Result<()> {
+        // rows already violating the schema make this fail instead of hiding corruption
+        let sql = format!(
+            "ALTER TABLE {table} DROP CONSTRAINT IF EXISTS {constraint};
+            ALTER TABLE {table} ADD CONSTRAINT {constraint} CHECK ({check});",
+            table = table_ref(db_name, table_name)?,
+            constraint = quote_ident(&format!("{}_data_schema", table_name))?,
+            check = schema_to_check(schema)
+        );
+        debug!("{}", sql);
+        self.pool.get().await?.batch_execute(&sql).await?;
+        Ok(())
+    }
+
+    async fn install_search(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        search: &SearchConfig,
+    ) -> Result<()> {
+        self.install_search_column(&table_ref(db_name, table_name)?, table_name, search)
             .await
-            .map(|_| ())
-            .map_err(|e| anyhow::Error::from(e))
     }
 
     async fn select_by_id(&self, db_name: &str, table_name: &str, id: &str) -> Result<Doc<T>> {
         let sql = format!(
-            "SELECT * FROM {}.{} WHERE _id = '{}'",
-            db_name, table_name, id
+            "SELECT * FROM {} WHERE _id = $1",
+            table_ref(db_name, table_name)?
         );
-        let conn = self.pool.get().await?;
-        let rows = conn.query(&sql, &[]).await?;
+        let conn = self.read_conn().await?;
+        let rows = conn.query(&sql, &[&id]).await?;
         if rows.is_empty() {
-            bail!("Document not found");
+            return Err(NotFound.into());
         }
-        let row = rows.get(0).unwrap();
+        let row = rows.first().unwrap();
         let data: Value = row.get("data");
         let data: T = serde_json::from_value(data)?;
         let doc = Doc {
@@ -73,10 +181,31 @@ impl<T: RestModel> DbClient<T> for Db {
             data,
             _created_at: row.get("_created_at"),
             _updated_at: row.get("_updated_at"),
+            _expanded: Default::default(),
+            _highlights: Default::default(),
         };
         Ok(doc)
     }
 
+    async fn select_by_ids(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        ids: &[&str],
+    ) -> Result<Vec<Doc<T>>> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let sql = format!(
+            "SELECT * FROM {} WHERE _id = ANY($1)",
+            table_ref(db_name, table_name)?
+        );
+        debug!("{}", sql);
+        let conn = self.read_conn().await?;
+        let rows = conn.query(&sql, &[&ids]).await?;
+        rows.iter().map(row_to_doc).collect()
+    }
+
     async fn paginate(
         &self,
         db_name: &str,
@@ -87,45 +216,75 @@ impl<T: RestModel> DbClient<T> for Db {
         let limit = pagination_params.limit.unwrap_or(10).max(1);
         let offset = (page - 1) * limit;
 
+        let table = table_ref(db_name, table_name)?;
         let mut seq = 1u32;
-        let mut bindings = vec![];
+        let mut bindings: Bindings = vec![];
 
-        let where_sql = if let Some(ref filter) = pagination_params.filter {
+        let mut conditions = vec![];
+        if let Some(ref filter) = pagination_params.filter {
             let sql = cond_to_sql(filter, &mut bindings, &mut seq)?;
-            if sql.is_empty() {
-                "".to_string()
-            } else {
-                format!("WHERE {}", sql)
+            if !sql.is_empty() {
+                conditions.push(sql);
             }
-        } else {
+        }
+        let search = match &pagination_params.q {
+            Some(q) => {
+                let config = T::get_search()
+                    .ok_or_else(|| anyhow!("{} has no search fields", table_name))?;
+                let search = SearchSql::new(q, &config, &mut bindings, &mut seq);
+                conditions.push(search.filter());
+                Some((config, search))
+            }
+            None => None,
+        };
+        let where_sql = if conditions.is_empty() {
             "".to_string()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
         };
 
-        // 处理排序
-        let order_sql = if let Some(sort_expr) = &pagination_params.sort {
-            sort_to_sql(sort_expr)?
-        } else {
-            "_id ASC".to_string()
+        let filter_bindings = bindings.len();
+
+        // 处理排序，搜索时默认按相关度
+        let rank = search.as_ref().map(|(_, search)| search.rank());
+        let order_sql = match (&pagination_params.sort, &rank) {
+            (Some(sort_expr), _) => sort_to_sql(
+                sort_expr,
+                T::get_sort_types(),
+                rank.as_deref(),
+                &mut bindings,
+                &mut seq,
+            )?,
+            (None, Some(rank)) => format!("{} DESC, _id ASC", rank),
+            (None, None) => "_id ASC".to_string(),
         };
+        let highlights_sql = match &search {
+            Some((config, search)) => format!(
+                ", {} AS _highlights",
+                search.highlights(config, &mut bindings, &mut seq)
+            ),
+            None => "".to_string(),
+        };
+        let limit_sql = bind(limit as i64, &mut bindings, &mut seq);
+        let offset_sql = bind(offset as i64, &mut bindings, &mut seq);
 
         // 查询分页数据
         let query_sql = format!(
-            "SELECT * FROM {}.{} {} ORDER BY {} LIMIT {} OFFSET {}",
-            db_name, table_name, where_sql, order_sql, limit, offset
+            "SELECT *{} FROM {} {} ORDER BY {} LIMIT {} OFFSET {}",
+            highlights_sql, table, where_sql, order_sql, limit_sql, offset_sql
         );
 
-        // 查询总数
-        let total_sql = format!(
-            "SELECT COUNT(*) FROM {}.{} {}",
-            db_name, table_name, where_sql
-        );
+        // 查询总数，只用过滤条件的参数
+        let total_sql = format!("SELECT COUNT(*) FROM {} {}", table, where_sql);
 
-        let conn = self.pool.get().await?;
-        let args: Vec<&(dyn ToSql + Sync)> =
-            bindings.iter().map(|v| v.as_ref()).collect::<Vec<_>>();
+        let conn = self.read_conn().await?;
+        let args: Vec<&(dyn ToSql + Sync)> = bindings
+            .iter()
+            .map(|v| v.as_ref() as &(dyn ToSql + Sync))
+            .collect::<Vec<_>>();
         debug!("args: {:?}", args);
         debug!("total_sql: {}", total_sql);
-        let row = conn.query_one(&total_sql, &args).await?;
+        let row = conn.query_one(&total_sql, &args[..filter_bindings]).await?;
         let total_count: i64 = row.get(0);
         let total_count = total_count as u32;
 
@@ -141,6 +300,14 @@ impl<T: RestModel> DbClient<T> for Db {
                     data,
                     _created_at: row.get(2),
                     _updated_at: row.get(3),
+                    _expanded: Default::default(),
+                    _highlights: match search {
+                        Some(_) => match row.get("_highlights") {
+                            Value::Object(highlights) => highlights,
+                            _ => Default::default(),
+                        },
+                        None => Default::default(),
+                    },
                 };
                 items.push(doc);
             }
@@ -149,7 +316,12 @@ impl<T: RestModel> DbClient<T> for Db {
             vec![]
         };
 
-        let total_pages = total_count / limit + if total_count % limit > 0 { 1 } else { 0 };
+        let mut items = items;
+        if let Some(expand) = &pagination_params.expand {
+            DbClient::<T>::expand(self, &mut items, &expand_fields(expand)).await?;
+        }
+
+        let total_pages = total_count.div_ceil(limit);
         Ok(PaginationResult {
             items,
             pagination: Pagination {
@@ -161,23 +333,136 @@ impl<T: RestModel> DbClient<T> for Db {
         })
     }
 
+    async fn expand(&self, docs: &mut [Doc<T>], fields: &[&str]) -> Result<()> {
+        let data = docs
+            .iter()
+            .map(|doc| serde_json::to_value(&doc.data))
+            .collect::<serde_json::Result<Vec<_>>>()?;
+        for field in fields {
+            let reference = T::get_references()
+                .iter()
+                .find(|r| r.field == *field)
+                .ok_or_else(|| anyhow!("`{}` is not a reference", field))?;
+            let (db_name, table_name) = self
+                .namespace
+                .resolve((reference.db_name)(), (reference.table_name)())?;
+            let ids = data
+                .iter()
+                .map(|data| data.get(field).and_then(Value::as_str))
+                .collect::<Vec<_>>();
+            let mut unique = ids.iter().flatten().copied().collect::<Vec<_>>();
+            unique.sort_unstable();
+            unique.dedup();
+
+            // one batched lookup per reference field instead of one per document
+            let mut found = HashMap::new();
+            if !unique.is_empty() {
+                let sql = format!(
+                    "SELECT * FROM {} WHERE _id = ANY($1)",
+                    table_ref(&db_name, &table_name)?
+                );
+                debug!("{}", sql);
+                let conn = self.read_conn().await?;
+                for row in conn.query(&sql, &[&unique]).await? {
+                    let id: String = row.get("_id");
+                    let doc = serde_json::json!({
+                        "_id": id,
+                        "data": row.get::<_, Value>("data"),
+                        "_created_at": row.get::<_, i64>("_created_at"),
+                        "_updated_at": row.get::<_, i64>("_updated_at"),
+                    });
+                    found.insert(id, doc);
+                }
+            }
+            for (doc, id) in docs.iter_mut().zip(ids) {
+                let expanded = id.and_then(|id| found.get(id)).cloned();
+                doc._expanded
+                    .insert(field.to_string(), expanded.unwrap_or(Value::Null));
+            }
+        }
+        Ok(())
+    }
+
+    async fn aggregate(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        params: &AggregateParams,
+    ) -> Result<Vec<Value>> {
+        let mut bindings = vec![];
+        let sql = aggregate_to_sql(
+            &table_ref(db_name, table_name)?,
+            params,
+            &mut bindings,
+            &mut 1,
+        )?;
+        debug!("{}", sql);
+        let args_refs: Vec<&(dyn ToSql + Sync)> = bindings
+            .iter()
+            .map(|x| x.as_ref() as &(dyn ToSql + Sync))
+            .collect();
+        let conn = self.read_conn().await?;
+        let rows = conn.query(&sql, &args_refs).await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn distinct(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        field: &str,
+        filter: Option<&Condition>,
+        limit: Option<u32>,
+    ) -> Result<Vec<Facet>> {
+        let mut bindings = vec![];
+        let sql = distinct_to_sql(
+            &table_ref(db_name, table_name)?,
+            field,
+            filter,
+            limit,
+            &mut bindings,
+            &mut 1,
+        )?;
+        debug!("{}", sql);
+        let args_refs: Vec<&(dyn ToSql + Sync)> = bindings
+            .iter()
+            .map(|x| x.as_ref() as &(dyn ToSql + Sync))
+            .collect();
+        let conn = self.read_conn().await?;
+        let rows = conn.query(&sql, &args_refs).await?;
+        Ok(rows
+            .iter()
+            .map(|row| Facet {
+                value: row.get(0),
+                count: row.get::<_, i64>(1) as u32,
+            })
+            .collect())
+    }
+
     async fn upsert(
         &self,
         db_name: &str,
         table_name: &str,
         items: &[Doc<T>],
-    ) -> Result<UpsertResult> {
+        returning: Returning,
+    ) -> Result<UpsertResult<T>> {
         if items.is_empty() {
+            let (ids, docs) = returned(&[], returning)?;
             return Ok(UpsertResult {
                 created_count: 0,
                 updated_count: 0,
+                ids,
+                docs,
             });
         }
 
-        let mut query = format!("INSERT INTO {}.{} (_id, data) VALUES ", db_name, table_name);
+        let mut query = format!(
+            "INSERT INTO {} (_id, data) VALUES ",
+            table_ref(db_name, table_name)?
+        );
 
         let mut values = Vec::new();
-        let mut args: Vec<Box<dyn ToSql + Sync>> = Vec::new();
+        let mut args: Vec<Box<dyn ToSql + Sync + Send>> = Vec::new();
 
         for (i, doc) in items.iter().enumerate() {
             values.push(format!("(${}, ${})", i * 2 + 1, i * 2 + 2,));
@@ -187,40 +472,304 @@ impl<T: RestModel> DbClient<T> for Db {
         }
 
         query.push_str(&values.join(", "));
-        query.push_str(
+        query.push_str(&format!(
             " ON CONFLICT (_id) DO UPDATE SET
               data = EXCLUDED.data,
               _updated_at = (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT
-            RETURNING (xmax = 0) AS inserted;",
-        );
+            RETURNING (xmax = 0) AS inserted, {};",
+            returning_columns(returning)
+        ));
 
         debug!("{}", query);
 
         let conn = self.pool.get().await?;
-        let args_refs: Vec<&(dyn ToSql + Sync)> = args.iter().map(|x| x.as_ref()).collect();
+        let args_refs: Vec<&(dyn ToSql + Sync)> = args
+            .iter()
+            .map(|x| x.as_ref() as &(dyn ToSql + Sync))
+            .collect();
         let rows = conn.query(&query, &args_refs[..]).await?;
 
-        let created_count = rows.iter().filter(|row| row.get::<_, bool>(0)).count() as u32;
+        let created_count = rows
+            .iter()
+            .filter(|row| row.get::<_, bool>("inserted"))
+            .count() as u32;
         let updated_count = rows.len() as u32 - created_count;
+        let (ids, docs) = returned(&rows, returning)?;
 
         Ok(UpsertResult {
             created_count,
             updated_count,
+            ids,
+            docs,
         })
     }
 
+    async fn insert(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        on_conflict: OnConflict,
+    ) -> Result<InsertResult> {
+        if items.is_empty() {
+            return Ok(InsertResult {
+                created_count: 0,
+                created_ids: vec![],
+                skipped_ids: vec![],
+            });
+        }
+
+        let mut query = format!(
+            "INSERT INTO {} (_id, data) VALUES ",
+            table_ref(db_name, table_name)?
+        );
+        let mut values = Vec::new();
+        let mut args: Vec<Box<dyn ToSql + Sync + Send>> = Vec::new();
+        for (i, doc) in items.iter().enumerate() {
+            values.push(format!("(${}, ${})", i * 2 + 1, i * 2 + 2));
+            args.push(Box::new(doc._id.clone()));
+            args.push(Box::new(serde_json::to_value(&doc.data)?));
+        }
+        query.push_str(&values.join(", "));
+        query.push_str(" ON CONFLICT (_id) DO NOTHING RETURNING _id;");
+        debug!("{}", query);
+
+        let mut conn = self.pool.get().await?;
+        let tx = conn.transaction().await?;
+        let args_refs: Vec<&(dyn ToSql + Sync)> = args
+            .iter()
+            .map(|x| x.as_ref() as &(dyn ToSql + Sync))
+            .collect();
+        let rows = tx.query(&query, &args_refs[..]).await?;
+
+        // an id repeated within `items` is only created once; later copies count as skipped
+        let mut inserted: HashSet<String> = rows.iter().map(|row| row.get(0)).collect();
+        let mut created_ids = vec![];
+        let mut skipped_ids = vec![];
+        for doc in items {
+            if inserted.remove(&doc._id) {
+                created_ids.push(doc._id.clone());
+            } else {
+                skipped_ids.push(doc._id.clone());
+            }
+        }
+        if on_conflict == OnConflict::Fail && !skipped_ids.is_empty() {
+            tx.rollback().await?;
+            return Err(Conflict { ids: skipped_ids }.into());
+        }
+        tx.commit().await?;
+
+        Ok(InsertResult {
+            created_count: created_ids.len() as u32,
+            created_ids,
+            skipped_ids,
+        })
+    }
+
+    async fn replace(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+    ) -> Result<ReplaceResult> {
+        if items.is_empty() {
+            return Ok(ReplaceResult {
+                replaced_count: 0,
+                replaced_ids: vec![],
+            });
+        }
+
+        let mut values = Vec::new();
+        let mut args: Vec<Box<dyn ToSql + Sync + Send>> = Vec::new();
+        for (i, doc) in items.iter().enumerate() {
+            values.push(format!("(${}::TEXT, ${}::JSONB)", i * 2 + 1, i * 2 + 2));
+            args.push(Box::new(doc._id.clone()));
+            args.push(Box::new(serde_json::to_value(&doc.data)?));
+        }
+        let query = format!(
+            "UPDATE {} AS t SET
+              data = v.data,
+              _updated_at = (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT
+            FROM (VALUES {}) AS v(_id, data)
+            WHERE t._id = v._id
+            RETURNING t._id;",
+            table_ref(db_name, table_name)?,
+            values.join(", ")
+        );
+        debug!("{}", query);
+
+        let mut conn = self.pool.get().await?;
+        let tx = conn.transaction().await?;
+        let args_refs: Vec<&(dyn ToSql + Sync)> = args
+            .iter()
+            .map(|x| x.as_ref() as &(dyn ToSql + Sync))
+            .collect();
+        let rows = tx.query(&query, &args_refs[..]).await?;
+
+        let replaced: HashSet<String> = rows.iter().map(|row| row.get(0)).collect();
+        let missing = items
+            .iter()
+            .filter(|doc| !replaced.contains(&doc._id))
+            .map(|doc| doc._id.clone())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            tx.rollback().await?;
+            return Err(Missing { ids: missing }.into());
+        }
+        tx.commit().await?;
+
+        let mut seen = HashSet::new();
+        let replaced_ids = items
+            .iter()
+            .filter(|doc| seen.insert(doc._id.as_str()))
+            .map(|doc| doc._id.clone())
+            .collect::<Vec<_>>();
+        Ok(ReplaceResult {
+            replaced_count: replaced.len() as u32,
+            replaced_ids,
+        })
+    }
+
+    async fn upsert_guarded(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        guard: &WriteGuard,
+    ) -> Result<UpsertResult<T>> {
+        if items.is_empty() {
+            return Ok(UpsertResult {
+                created_count: 0,
+                updated_count: 0,
+                ids: None,
+                docs: None,
+            });
+        }
+
+        let mut values = Vec::new();
+        let mut data = Vec::new();
+        let mut args: Vec<Box<dyn ToSql + Sync + Send>> = Vec::new();
+        for (i, doc) in items.iter().enumerate() {
+            values.push(format!("(${}, ${})", i * 2 + 1, i * 2 + 2));
+            let value = serde_json::to_value(&doc.data)?;
+            args.push(Box::new(doc._id.clone()));
+            args.push(Box::new(value.clone()));
+            data.push(value);
+        }
+        let protected = args.len() + 1;
+        args.push(Box::new(guard.protected.clone()));
+        // rows the filter skips, including where it is NULL, are missing from
+        // RETURNING; the subquery scopes its columns to the stored row, as
+        // `EXCLUDED` has the same ones
+        let mut where_sql = String::new();
+        if let Some(filter) = &guard.filter {
+            let seq = &mut (args.len() as u32 + 1);
+            let sql = cond_to_sql(filter, &mut args, seq)?;
+            if !sql.is_empty() {
+                where_sql = format!("WHERE EXISTS (SELECT FROM (SELECT t.*) AS t WHERE {})", sql);
+            }
+        }
+        // an overwrite takes the protected fields from the stored row, dropping those it lacks
+        let query = format!(
+            "INSERT INTO {} AS t (_id, data) VALUES {} ON CONFLICT (_id) DO UPDATE SET
+              data = (EXCLUDED.data - ${protected}::TEXT[]) || COALESCE(
+                (SELECT jsonb_object_agg(key, value) FROM jsonb_each(t.data)
+                WHERE key = ANY(${protected}::TEXT[])),
+                '{{}}'::JSONB
+              ),
+              _updated_at = (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT
+            {}
+            RETURNING (xmax = 0) AS inserted, _id;",
+            table_ref(db_name, table_name)?,
+            values.join(", "),
+            where_sql
+        );
+        debug!("{}", query);
+
+        let mut conn = self.pool.get().await?;
+        let tx = conn.transaction().await?;
+        let args_refs: Vec<&(dyn ToSql + Sync)> = args
+            .iter()
+            .map(|x| x.as_ref() as &(dyn ToSql + Sync))
+            .collect();
+        let rows = tx.query(&query, &args_refs[..]).await?;
+
+        let written: HashSet<String> = rows.iter().map(|row| row.get("_id")).collect();
+        if let Some(doc) = items.iter().find(|doc| !written.contains(&doc._id)) {
+            tx.rollback().await?;
+            return Err(Denied::new(format!("cannot overwrite {}", doc._id)).into());
+        }
+        let inserted: HashSet<String> = rows
+            .iter()
+            .filter(|row| row.get::<_, bool>("inserted"))
+            .map(|row| row.get("_id"))
+            .collect();
+        if guard.replace_only && !inserted.is_empty() {
+            tx.rollback().await?;
+            let missing = items
+                .iter()
+                .filter(|doc| inserted.contains(&doc._id))
+                .map(|doc| doc._id.clone())
+                .collect();
+            return Err(Missing { ids: missing }.into());
+        }
+        for (doc, data) in items.iter().zip(&data) {
+            if inserted.contains(&doc._id) {
+                if let Err(denied) = guard.check_new(data) {
+                    tx.rollback().await?;
+                    return Err(denied.into());
+                }
+            }
+        }
+        tx.commit().await?;
+
+        let created_count = inserted.len() as u32;
+        Ok(UpsertResult {
+            created_count,
+            updated_count: rows.len() as u32 - created_count,
+            ids: None,
+            docs: None,
+        })
+    }
+
+    async fn copy_in(&self, db_name: &str, table_name: &str, items: &[Doc<T>]) -> Result<u32> {
+        // unlike `upsert`, keeps the documents' own timestamps
+        let sql = format!(
+            "COPY {} (_id, data, _created_at, _updated_at) FROM STDIN BINARY",
+            table_ref(db_name, table_name)?
+        );
+        debug!("{}", sql);
+        let conn = self.pool.get().await?;
+        let sink = conn.copy_in(&sql).await?;
+        let writer =
+            BinaryCopyInWriter::new(sink, &[Type::VARCHAR, Type::JSONB, Type::INT8, Type::INT8]);
+        let mut writer = std::pin::pin!(writer);
+        for doc in items {
+            let data = serde_json::to_value(&doc.data)?;
+            writer
+                .as_mut()
+                .write(&[&doc._id, &data, &doc._created_at, &doc._updated_at])
+                .await?;
+        }
+        Ok(writer.finish().await? as u32)
+    }
+
     async fn update(
         &self,
         db_name: &str,
         table_name: &str,
         params: &PatchParams,
-    ) -> Result<UpdateResult> {
+        returning: Returning,
+    ) -> Result<UpdateResult<T>> {
         // 2️⃣ 解析 `patch` 生成 `JSONB SET` 语句
         let mut set_sql = "data = ".to_string();
-        let mut args: Vec<Box<dyn ToSql + Sync>> = Vec::new();
+        let mut args: Vec<Box<dyn ToSql + Sync + Send>> = Vec::new();
         let mut jsonb_expr = "data".to_string(); // 初始值为 `data`
 
-        for (key, value) in params.patch.as_object().unwrap() {
+        let Some(patch) = params.patch.as_object() else {
+            bail!("patch must be an object");
+        };
+        for (key, value) in patch {
             let path_arg_index = args.len() + 1;
             let value_arg_index = path_arg_index + 1;
 
@@ -244,25 +793,34 @@ impl<T: RestModel> DbClient<T> for Db {
         // 1️⃣ 解析 `filter` 生成 `WHERE` 语句
         let mut bindings = vec![];
         let seq = &mut (args.len() as u32 + 1);
-        let where_sql = cond_to_sql(&params.filter, &mut bindings, seq)?;
+        let where_sql = format!("WHERE {}", cond_to_sql(&params.filter, &mut bindings, seq)?);
 
         // 3️⃣ 生成 SQL
         let query = format!(
-            "UPDATE {}.{} SET {} {} RETURNING _id;",
-            db_name, table_name, set_sql, where_sql
+            "UPDATE {} SET {} {} RETURNING {};",
+            table_ref(db_name, table_name)?,
+            set_sql,
+            where_sql,
+            returning_columns(returning)
         );
         args.append(&mut bindings);
 
         // 4️⃣ 执行 SQL
         debug!("{}", query);
         debug!("{:?}", args);
-        let args_refs: Vec<&(dyn ToSql + Sync)> = args.iter().map(|x| x.as_ref()).collect();
+        let args_refs: Vec<&(dyn ToSql + Sync)> = args
+            .iter()
+            .map(|x| x.as_ref() as &(dyn ToSql + Sync))
+            .collect();
         let conn = self.pool.get().await?;
         let rows = conn.query(&query, &args_refs[..]).await?;
 
         // 5️⃣ 返回更新的行数
+        let (ids, docs) = returned(&rows, returning)?;
         Ok(UpdateResult {
             updated_count: rows.len() as u32,
+            ids,
+            docs,
         })
     }
 
@@ -271,26 +829,105 @@ impl<T: RestModel> DbClient<T> for Db {
         db_name: &str,
         table_name: &str,
         params: &DeleteParams,
-    ) -> Result<DeleteResult> {
+        returning: Returning,
+    ) -> Result<DeleteResult<T>> {
         // 1️⃣ 解析 `filter` 生成 `WHERE` 语句
         let bindings = &mut vec![];
         let seq = &mut 1;
-        let where_sql = cond_to_sql(&params.filter, bindings, seq)?;
+        let where_sql = format!("WHERE {}", cond_to_sql(&params.filter, bindings, seq)?);
 
         // 2️⃣ 生成 SQL
+        let table = table_ref(db_name, table_name)?;
         let query = format!(
-            "DELETE FROM {}.{} {} RETURNING _id;",
-            db_name, table_name, where_sql
+            "DELETE FROM {} {} RETURNING {};",
+            table,
+            where_sql,
+            returning_columns(returning)
         );
 
-        // 3️⃣ 执行 SQL
-        let conn = self.pool.get().await?;
-        let args_refs: Vec<&(dyn ToSql + Sync)> = bindings.iter().map(|x| x.as_ref()).collect();
-        let rows = conn.query(&query, &args_refs).await?;
+        // 3️⃣ 执行 SQL，连同引用它们的文档一起处理
+        let mut conn = self.pool.get().await?;
+        let tx = conn.transaction().await?;
+        let args_refs: Vec<&(dyn ToSql + Sync)> = bindings
+            .iter()
+            .map(|x| x.as_ref() as &(dyn ToSql + Sync))
+            .collect();
+        let mut cascade = Cascade::default();
+        let rows = if T::get_referrers().is_empty() {
+            tx.query(&query, &args_refs).await?
+        } else {
+            // lock the documents first, so the references are checked against
+            // exactly the ones deleted
+            let lock = format!("SELECT _id FROM {} {} FOR UPDATE", table, where_sql);
+            debug!("{}", lock);
+            let ids: Vec<String> = tx
+                .query(&lock, &args_refs)
+                .await?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            on_delete(
+                &tx,
+                &self.namespace,
+                (T::get_db_name(), T::get_table_name()),
+                T::get_referrers(),
+                ids.clone(),
+                &mut cascade,
+            )
+            .await?;
+            let query = format!(
+                "DELETE FROM {} WHERE _id = ANY($1) RETURNING {};",
+                table,
+                returning_columns(returning)
+            );
+            debug!("{}", query);
+            tx.query(&query, &[&ids]).await?
+        };
+        tx.commit().await?;
 
         // 4️⃣ 返回删除的行数
+        let (ids, docs) = returned(&rows, returning)?;
         Ok(DeleteResult {
             deleted_count: rows.len() as u32,
+            ids,
+            docs,
+            cascaded: cascade.cascaded,
+            nullified: cascade.nullified,
         })
     }
 }
+
+fn row_to_doc<T: RestModel>(row: &Row) -> Result<Doc<T>> {
+    let data: Value = row.get("data");
+    Ok(Doc {
+        _id: row.get("_id"),
+        data: serde_json::from_value(data)?,
+        _created_at: row.get("_created_at"),
+        _updated_at: row.get("_updated_at"),
+        _expanded: Default::default(),
+        _highlights: Default::default(),
+    })
+}
+
+/// Columns a write must `RETURN` to fill in `returned`.
+fn returning_columns(returning: Returning) -> &'static str {
+    match returning {
+        Returning::None | Returning::Ids => "_id",
+        Returning::Docs => "_id, data, _created_at, _updated_at",
+    }
+}
+
+type Returned<T> = (Option<Vec<String>>, Option<Vec<Doc<T>>>);
+
+/// The `ids` and `docs` of a write result, from rows selected by `returning_columns`.
+fn returned<T: RestModel>(rows: &[Row], returning: Returning) -> Result<Returned<T>> {
+    let ids = || rows.iter().map(|row| row.get("_id")).collect();
+    Ok(match returning {
+        Returning::None => (None, None),
+        Returning::Ids => (Some(ids()), None),
+        Returning::Docs => (
+            Some(ids()),
+            Some(rows.iter().map(row_to_doc).collect::<Result<_>>()?),
+        ),
+    })
+}
@@ -0,0 +1,126 @@
+use std::{
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+
+use bb8_postgres::{
+    bb8::{Pool, PooledConnection, RunError, State},
+    PostgresConnectionManager,
+};
+use tokio_postgres::{Client, NoTls};
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+/// Bounds the extra connect `PgPool::get` makes to learn why it timed out.
+const DIAGNOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A connection pool to one server, over plain TCP or rustls.
+///
+/// `DbBuilder` makes one; an existing bb8 pool of either kind converts with
+/// `From`, so `Db::from_pool` takes it as is.
+#[derive(Debug, Clone)]
+pub struct PgPool {
+    inner: Inner,
+}
+
+#[derive(Debug, Clone)]
+enum Inner {
+    NoTls(Pool<PostgresConnectionManager<NoTls>>),
+    Rustls(Pool<PostgresConnectionManager<MakeRustlsConnect>>),
+}
+
+/// A connection checked out of a `PgPool`, returned to it on drop.
+pub enum PgConnection<'a> {
+    NoTls(PooledConnection<'a, PostgresConnectionManager<NoTls>>),
+    Rustls(PooledConnection<'a, PostgresConnectionManager<MakeRustlsConnect>>),
+}
+
+impl PgPool {
+    /// Checks out a connection, waiting up to the pool's connection timeout.
+    ///
+    /// bb8 retries failed connects quietly and only reports `TimedOut`, so
+    /// when the pool has no connection at all this tries one more connect
+    /// and returns its error instead, e.g. bad credentials or an unknown
+    /// database, which retrying won't fix.
+    pub async fn get(&self) -> Result<PgConnection<'_>, RunError<tokio_postgres::Error>> {
+        let result = match &self.inner {
+            Inner::NoTls(pool) => pool.get().await.map(PgConnection::NoTls),
+            Inner::Rustls(pool) => pool.get().await.map(PgConnection::Rustls),
+        };
+        match result {
+            Err(RunError::TimedOut) if self.state().connections == 0 => {
+                match tokio::time::timeout(DIAGNOSE_TIMEOUT, self.dedicated_connection()).await {
+                    Ok(Err(error)) => Err(RunError::User(error)),
+                    _ => Err(RunError::TimedOut),
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Opens a connection the pool doesn't manage or count; it closes on drop.
+    pub async fn dedicated_connection(&self) -> Result<Client, tokio_postgres::Error> {
+        match &self.inner {
+            Inner::NoTls(pool) => pool.dedicated_connection().await,
+            Inner::Rustls(pool) => pool.dedicated_connection().await,
+        }
+    }
+
+    /// The bb8 pool underneath, when it connects over plain TCP.
+    pub fn no_tls(&self) -> Option<&Pool<PostgresConnectionManager<NoTls>>> {
+        match &self.inner {
+            Inner::NoTls(pool) => Some(pool),
+            Inner::Rustls(_) => None,
+        }
+    }
+
+    /// The bb8 pool underneath, when it connects over rustls.
+    pub fn rustls(&self) -> Option<&Pool<PostgresConnectionManager<MakeRustlsConnect>>> {
+        match &self.inner {
+            Inner::NoTls(_) => None,
+            Inner::Rustls(pool) => Some(pool),
+        }
+    }
+
+    pub fn state(&self) -> State {
+        match &self.inner {
+            Inner::NoTls(pool) => pool.state(),
+            Inner::Rustls(pool) => pool.state(),
+        }
+    }
+}
+
+impl From<Pool<PostgresConnectionManager<NoTls>>> for PgPool {
+    fn from(pool: Pool<PostgresConnectionManager<NoTls>>) -> Self {
+        Self {
+            inner: Inner::NoTls(pool),
+        }
+    }
+}
+
+impl From<Pool<PostgresConnectionManager<MakeRustlsConnect>>> for PgPool {
+    fn from(pool: Pool<PostgresConnectionManager<MakeRustlsConnect>>) -> Self {
+        Self {
+            inner: Inner::Rustls(pool),
+        }
+    }
+}
+
+impl Deref for PgConnection<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        match self {
+            PgConnection::NoTls(conn) => conn,
+            PgConnection::Rustls(conn) => conn,
+        }
+    }
+}
+
+impl DerefMut for PgConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Client {
+        match self {
+            PgConnection::NoTls(conn) => conn,
+            PgConnection::Rustls(conn) => conn,
+        }
+    }
+}
@@ -1,13 +1,44 @@
 use anyhow::{bail, Result};
-use rest_model::Condition;
+use rest_model::{
+    check_identifier,
+    pagination::{parse_sort, Nulls},
+    Accumulator, AggregateParams, Condition, FieldSort, SortType,
+};
 use serde_json::Value;
 use tokio_postgres::types::ToSql;
 
-pub fn cond_to_sql(
-    cond: &Condition,
-    bindings: &mut Vec<Box<dyn ToSql + Sync>>,
+pub type Bindings = Vec<Box<dyn ToSql + Sync + Send>>;
+
+/// `name` validated by `check_identifier` and double-quoted. Lowercased like
+/// Postgres folds unquoted names, so tables created before quoting still match.
+pub fn quote_ident(name: &str) -> Result<String> {
+    check_identifier(name)?;
+    Ok(format!("\"{}\"", name.to_ascii_lowercase()))
+}
+
+/// `"database"."schema"."table"` for a resolved `db_name`/`table_name`.
+pub fn table_ref(db_name: &str, table_name: &str) -> Result<String> {
+    db_name
+        .split('.')
+        .chain([table_name])
+        .map(quote_ident)
+        .collect::<Result<Vec<_>>>()
+        .map(|segments| segments.join("."))
+}
+
+/// Binds `value` as the next parameter and returns its placeholder.
+pub fn bind(
+    value: impl ToSql + Sync + Send + 'static,
+    bindings: &mut Bindings,
     seq: &mut u32,
-) -> Result<String> {
+) -> String {
+    let placeholder = format!("${}", *seq);
+    *seq += 1;
+    bindings.push(Box::new(value));
+    placeholder
+}
+
+pub fn cond_to_sql(cond: &Condition, bindings: &mut Bindings, seq: &mut u32) -> Result<String> {
     match cond {
         Condition::And(conds) => Ok(format!(
             "({})",
@@ -26,140 +57,272 @@ pub fn cond_to_sql(
                 .join(" OR "),
         )),
         Condition::Not(cond) => Ok(format!("(NOT ({}))", cond_to_sql(cond, bindings, seq)?)),
-        Condition::Regex(field, value) => {
-            check_invalid_chars(field)?;
-            let s = *seq;
-            *seq += 1;
-            match value {
-                Value::String(v) => {
-                    bindings.push(Box::new(v.clone()));
-                    Ok(format!("{} ~ ${}", field_to_key_t(&field), s))
-                }
-                _ => {
-                    bail!("Invalid value for Regex")
-                }
+        Condition::Regex(field, value) => match value {
+            Value::String(v) => {
+                let key = field_to_key_t(field, bindings, seq)?;
+                Ok(format!("{} ~ {}", key, bind(v.clone(), bindings, seq)))
             }
-        }
-        Condition::Regexi(field, value) => {
-            check_invalid_chars(field)?;
-            let s = *seq;
-            *seq += 1;
-            match value {
-                Value::String(v) => {
-                    bindings.push(Box::new(v.clone()));
-                    Ok(format!("{} ~* ${}", field_to_key_t(&field), s))
-                }
-                _ => {
-                    bail!("Invalid value for Regexi")
-                }
+            _ => {
+                bail!("Invalid value for Regex")
             }
-        }
-        Condition::Eq(field, value) => {
-            check_invalid_chars(field)?;
-            normal_comparison(seq, bindings, field, "=", value)
-        }
-        Condition::Ne(field, value) => {
-            check_invalid_chars(field)?;
-            normal_comparison(seq, bindings, field, "!=", value)
-        }
-        Condition::Gt(field, value) => {
-            check_invalid_chars(field)?;
-            normal_comparison(seq, bindings, field, ">", value)
-        }
-        Condition::Gte(field, value) => {
-            check_invalid_chars(field)?;
-            normal_comparison(seq, bindings, field, ">=", value)
-        }
-        Condition::Lt(field, value) => {
-            check_invalid_chars(field)?;
-            normal_comparison(seq, bindings, field, "<", value)
-        }
-        Condition::Lte(field, value) => {
-            check_invalid_chars(field)?;
-            normal_comparison(seq, bindings, field, "<=", value)
-        }
-        Condition::In(field, value) => {
-            check_invalid_chars(field)?;
-            array_comparison(seq, bindings, field, value)
-        }
-        Condition::Nin(field, value) => {
-            check_invalid_chars(field)?;
+        },
+        Condition::Regexi(field, value) => match value {
+            Value::String(v) => {
+                let key = field_to_key_t(field, bindings, seq)?;
+                Ok(format!("{} ~* {}", key, bind(v.clone(), bindings, seq)))
+            }
+            _ => {
+                bail!("Invalid value for Regexi")
+            }
+        },
+        Condition::Eq(field, value) => normal_comparison(seq, bindings, field, "=", value),
+        Condition::Ne(field, value) => normal_comparison(seq, bindings, field, "!=", value),
+        Condition::Gt(field, value) => normal_comparison(seq, bindings, field, ">", value),
+        Condition::Gte(field, value) => normal_comparison(seq, bindings, field, ">=", value),
+        Condition::Lt(field, value) => normal_comparison(seq, bindings, field, "<", value),
+        Condition::Lte(field, value) => normal_comparison(seq, bindings, field, "<=", value),
+        Condition::In(field, value) => array_comparison(seq, bindings, field, value),
+        Condition::Nin(field, value) => Ok(format!(
+            "(NOT ({}))",
+            array_comparison(seq, bindings, field, value)?,
+        )),
+    }
+}
+
+/// `ORDER BY` terms for `sort_expr`, comparing each field per its explicit
+/// type or `sort_types`, and ending with `_id` so pages are stable. `rank`
+/// is the relevance of a search, for the `_rank` key.
+pub fn sort_to_sql(
+    sort_expr: &str,
+    sort_types: &[FieldSort],
+    rank: Option<&str>,
+    bindings: &mut Bindings,
+    seq: &mut u32,
+) -> Result<String> {
+    let mut order_by_clauses = vec![];
+    let mut by_id = false;
+    for key in parse_sort(sort_expr)? {
+        let sort_type = key.sort_type.unwrap_or_else(|| {
+            sort_types
+                .iter()
+                .find(|s| s.field == key.field)
+                .map_or(SortType::Text, |s| s.sort_type)
+        });
+        let expr = match key.field.as_str() {
+            "_id" => {
+                by_id = true;
+                key.field.clone()
+            }
+            "_created_at" | "_updated_at" => key.field.clone(),
+            "_rank" => match rank {
+                Some(rank) => rank.to_string(),
+                None => bail!("Sorting by _rank needs a search"),
+            },
+            field => match sort_type {
+                SortType::Text => field_to_key_t(field, bindings, seq)?,
+                SortType::Number => field_to_number(field, bindings, seq)?,
+                SortType::Timestamp => field_to_timestamp(field, bindings, seq)?,
+            },
+        };
+        let order = if key.descending { "DESC" } else { "ASC" };
+        let nulls = match key.nulls {
+            Some(Nulls::First) => " NULLS FIRST",
+            Some(Nulls::Last) => " NULLS LAST",
+            None => "",
+        };
+        order_by_clauses.push(format!("{} {}{}", expr, order, nulls));
+    }
+    if !by_id {
+        order_by_clauses.push("_id ASC".to_string());
+    }
+    Ok(order_by_clauses.join(", "))
+}
+
+/// A single `SELECT` returning one `jsonb` object per group of `table`.
+pub fn aggregate_to_sql(
+    table: &str,
+    params: &AggregateParams,
+    bindings: &mut Bindings,
+    seq: &mut u32,
+) -> Result<String> {
+    let mut pairs = vec![];
+    let mut groups = vec![];
+    for field in &params.group_by {
+        // `GROUP BY` repeats the same placeholder, so Postgres sees the same expression
+        let expr = field_to_json(field, bindings, seq)?;
+        pairs.push(format!(
+            "{}::TEXT, {}",
+            bind(field.clone(), bindings, seq),
+            expr
+        ));
+        groups.push(expr);
+    }
+    for (name, accumulator) in &params.accumulators {
+        let expr = match accumulator {
+            Accumulator::Count => "COUNT(*)".to_string(),
+            Accumulator::Sum(field) => format!("SUM({})", field_to_number(field, bindings, seq)?),
+            Accumulator::Avg(field) => format!("AVG({})", field_to_number(field, bindings, seq)?),
+            Accumulator::Min(field) => format!("MIN({})", field_to_number(field, bindings, seq)?),
+            Accumulator::Max(field) => format!("MAX({})", field_to_number(field, bindings, seq)?),
+        };
+        pairs.push(format!(
+            "{}::TEXT, {}",
+            bind(name.clone(), bindings, seq),
+            expr
+        ));
+    }
+
+    let mut sql = format!(
+        "SELECT jsonb_build_object({}) FROM {}",
+        pairs.join(", "),
+        table
+    );
+    if let Some(filter) = &params.filter {
+        sql.push_str(&format!(" WHERE {}", cond_to_sql(filter, bindings, seq)?));
+    }
+    if !groups.is_empty() {
+        let groups = groups.join(", ");
+        sql.push_str(&format!(" GROUP BY {} ORDER BY {}", groups, groups));
+    }
+    Ok(sql)
+}
+
+/// A `SELECT` of `(value jsonb, count bigint)` for each distinct value of `field`.
+pub fn distinct_to_sql(
+    table: &str,
+    field: &str,
+    filter: Option<&Condition>,
+    limit: Option<u32>,
+    bindings: &mut Bindings,
+    seq: &mut u32,
+) -> Result<String> {
+    let expr = field_to_json(field, bindings, seq)?;
+    let mut sql = format!(
+        "SELECT {e} AS value, COUNT(*) FROM {t} WHERE {e} IS NOT NULL AND jsonb_typeof({e}) <> 'null'",
+        e = expr,
+        t = table
+    );
+    if let Some(filter) = filter {
+        sql.push_str(&format!(" AND {}", cond_to_sql(filter, bindings, seq)?));
+    }
+    sql.push_str(" GROUP BY value ORDER BY COUNT(*) DESC, value");
+    if let Some(limit) = limit {
+        sql.push_str(&format!(" LIMIT {}", bind(limit as i64, bindings, seq)));
+    }
+    Ok(sql)
+}
+
+/// `field` as `jsonb`, including the system columns.
+fn field_to_json(field: &str, bindings: &mut Bindings, seq: &mut u32) -> Result<String> {
+    match field {
+        "_id" | "_created_at" | "_updated_at" => Ok(format!("to_jsonb({})", field)),
+        _ => field_to_key(field, bindings, seq),
+    }
+}
+
+/// `field` as `TIMESTAMPTZ` from an RFC 3339 string or epoch milliseconds,
+/// or `NULL` where it is neither.
+fn field_to_timestamp(field: &str, bindings: &mut Bindings, seq: &mut u32) -> Result<String> {
+    let path = bind(field_path(field)?, bindings, seq);
+    Ok(format!(
+        "CASE jsonb_typeof(data#>{p}::TEXT[]) \
+         WHEN 'number' THEN to_timestamp((data#>{p}::TEXT[])::FLOAT8 / 1000) \
+         WHEN 'string' THEN CASE WHEN data#>>{p}::TEXT[] ~ '{re}' \
+         THEN (data#>>{p}::TEXT[])::TIMESTAMPTZ END END",
+        p = path,
+        re = TIMESTAMP_PATTERN
+    ))
+}
+
+/// Strings `field_to_timestamp` casts; anything else would fail the whole query.
+const TIMESTAMP_PATTERN: &str =
+    r"^\d{4}-\d{2}-\d{2}([T ]\d{2}:\d{2}(:\d{2}(\.\d+)?)?)?(Z|[+-]\d{2}(:?\d{2})?)?$";
+
+/// `field` as `FLOAT8`, or `NULL` where it isn't a JSON number.
+fn field_to_number(field: &str, bindings: &mut Bindings, seq: &mut u32) -> Result<String> {
+    match field {
+        "_created_at" | "_updated_at" => Ok(format!("{}::FLOAT8", field)),
+        "_id" => bail!("Invalid numeric field {}", field),
+        _ => {
+            let key = field_to_key(field, bindings, seq)?;
             Ok(format!(
-                "(NOT ({}))",
-                array_comparison(seq, bindings, field, value)?,
+                "CASE WHEN jsonb_typeof({}) = 'number' THEN ({})::FLOAT8 END",
+                key, key
             ))
         }
     }
 }
 
-pub fn sort_to_sql(sort_expr: &str) -> Result<String> {
-    check_invalid_chars(sort_expr)?;
-    let order_by_clauses: Vec<String> = sort_expr
-        .split(|c| c == '+' || c == '-') // 按 `+` 和 `-` 拆分
-        .filter(|s| !s.is_empty()) // 去除空字符串
-        .map(|key| {
-            let order = if sort_expr.contains(&format!("+{}", key)) {
-                "ASC"
-            } else {
-                "DESC"
-            };
-            format!("{} {}", field_to_key_t(key), order) // 组合成 `key ASC/DESC`
-        })
-        .collect();
-
-    if order_by_clauses.is_empty() {
-        Ok("_id ASC".to_string())
-    } else {
-        Ok(format!("{}", order_by_clauses.join(", ")))
-    }
+fn field_to_key(field: &str, bindings: &mut Bindings, seq: &mut u32) -> Result<String> {
+    _field_to_key(field, false, bindings, seq)
 }
 
-fn field_to_key(field: &str) -> String {
-    _field_to_key(field, false)
+fn field_to_key_t(field: &str, bindings: &mut Bindings, seq: &mut u32) -> Result<String> {
+    _field_to_key(field, true, bindings, seq)
 }
 
-fn field_to_key_t(field: &str) -> String {
-    _field_to_key(field, true)
+/// `data#>$n::TEXT[]`, with the dotted `field` bound as the path.
+fn _field_to_key(
+    field: &str,
+    text: bool,
+    bindings: &mut Bindings,
+    seq: &mut u32,
+) -> Result<String> {
+    let path = field_path(field)?;
+    let t = if text { ">" } else { "" };
+    Ok(format!("data#>{}{}::TEXT[]", t, bind(path, bindings, seq)))
 }
 
-fn _field_to_key(field: &str, text: bool) -> String {
-    let v = field
-        .split(".")
-        .map(|s| format!("{}", s))
-        .collect::<Vec<_>>()
-        .join(",");
-    let t = if text { ">" } else { "" };
-    format!("data#>{}'{{{}}}'", t, v)
+/// The segments of a dotted field path; any characters are allowed since the
+/// path is bound as a parameter, but no segment may be empty.
+fn field_path(field: &str) -> Result<Vec<String>> {
+    let path = field.split('.').map(str::to_string).collect::<Vec<_>>();
+    if path.iter().any(String::is_empty) {
+        bail!("Invalid field {:?}", field);
+    }
+    Ok(path)
 }
 
 fn normal_comparison(
     seq: &mut u32,
-    bindings: &mut Vec<Box<dyn ToSql + Sync>>,
+    bindings: &mut Bindings,
     field: &str,
     op: &str,
     value: &Value,
 ) -> Result<String> {
-    let s = *seq;
-    *seq += 1;
     match value {
         Value::String(v) => match field {
-            "_id" => {
-                bindings.push(Box::new(v.clone()));
-                Ok(format!("{} {} ${}", field, op, s))
-            }
+            "_id" => Ok(format!(
+                "{} {} {}",
+                field,
+                op,
+                bind(v.clone(), bindings, seq)
+            )),
             _ => {
-                bindings.push(Box::new(value.clone()));
-                Ok(format!("{} {} ${}", field_to_key(field), op, s))
+                let key = field_to_key(field, bindings, seq)?;
+                Ok(format!(
+                    "{} {} {}",
+                    key,
+                    op,
+                    bind(value.clone(), bindings, seq)
+                ))
             }
         },
         Value::Number(v) => match field {
             "_created_at" | "_updated_at" => {
-                bindings.push(Box::new(v.as_i64().unwrap()));
-                Ok(format!("{} {} ${}", field, op, s))
+                let Some(v) = v.as_i64() else {
+                    bail!("Invalid value for field {}", field)
+                };
+                Ok(format!("{} {} {}", field, op, bind(v, bindings, seq)))
             }
             _ => {
-                bindings.push(Box::new(value.clone()));
-                Ok(format!("{} {} ${}", field_to_key(field), op, s))
+                let key = field_to_key(field, bindings, seq)?;
+                Ok(format!(
+                    "{} {} {}",
+                    key,
+                    op,
+                    bind(value.clone(), bindings, seq)
+                ))
             }
         },
         _ => {
@@ -170,90 +333,59 @@ fn normal_comparison(
 
 fn array_comparison(
     seq: &mut u32,
-    bindings: &mut Vec<Box<dyn ToSql + Sync>>,
+    bindings: &mut Bindings,
     field: &str,
     value: &Value,
 ) -> Result<String> {
-    match value {
-        Value::Array(arr) => {
-            if arr.is_empty() {
-                return Ok("FALSE".to_string());
-            } else {
-                let s = *seq;
-                *seq += 1;
-                match field {
-                    "_id" => {
-                        let arr = arr.iter().map(|v| v.as_str()).collect::<Vec<_>>();
-                        if arr.contains(&None) {
-                            bail!("Invalid value for field {}", field)
-                        } else {
-                            bindings.push(Box::new(
-                                arr.into_iter()
-                                    .map(|v| v.unwrap().to_string())
-                                    .collect::<Vec<_>>(),
-                            ));
-                            Ok(format!("{} = ANY(${})", field, s))
-                        }
-                    }
-                    "_created_at" | "_updated_at" => {
-                        let arr = arr.iter().map(|v| v.as_i64()).collect::<Vec<_>>();
-                        if arr.contains(&None) {
-                            bail!("Invalid value for field {}", field)
-                        } else {
-                            bindings.push(Box::new(
-                                arr.into_iter().map(|v| v.unwrap()).collect::<Vec<_>>(),
-                            ));
-                            Ok(format!("{} = ANY(${})", field, s))
-                        }
-                    }
-                    _ => {
-                        let array_type = match arr.first() {
-                            Some(Value::Number(_)) => "FLOAT8",
-                            Some(Value::String(_)) => "TEXT",
-                            _ => bail!("Invalid value for op IN"),
-                        };
-
-                        if array_type == "FLOAT8" {
-                            let arr = arr.iter().map(|v| v.as_f64()).collect::<Vec<_>>();
-                            if arr.contains(&None) {
-                                bail!("Invalid value for field {}", field)
-                            } else {
-                                bindings.push(Box::new(
-                                    arr.into_iter().map(|v| v.unwrap()).collect::<Vec<_>>(),
-                                ));
-                            }
-                            Ok(format!(
-                                "({})::{} = ANY(${})",
-                                field_to_key(field),
-                                array_type,
-                                s
-                            ))
-                        } else {
-                            let arr = arr.iter().map(|v| v.as_str()).collect::<Vec<_>>();
-                            if arr.contains(&None) {
-                                bail!("Invalid value for field {}", field)
-                            } else {
-                                bindings.push(Box::new(
-                                    arr.into_iter()
-                                        .map(|v| v.unwrap().to_string())
-                                        .collect::<Vec<_>>(),
-                                ));
-                            }
-                            Ok(format!("{} = ANY(${})", field_to_key_t(field), s))
-                        }
-                    }
-                }
-            }
-        }
-        _ => bail!("Invalid value for op IN"),
+    let Value::Array(arr) = value else {
+        bail!("Invalid value for op IN")
+    };
+    if arr.is_empty() {
+        return Ok("FALSE".to_string());
     }
-}
-
-fn check_invalid_chars(input: &str) -> Result<()> {
-    if input.contains('\'') || input.contains('(') || input.contains(')') || input.contains(';') {
-        bail!("Invalid characters in input");
+    match field {
+        "_id" => {
+            let Some(arr) = arr
+                .iter()
+                .map(|v| v.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+            else {
+                bail!("Invalid value for field {}", field)
+            };
+            Ok(format!("{} = ANY({})", field, bind(arr, bindings, seq)))
+        }
+        "_created_at" | "_updated_at" => {
+            let Some(arr) = arr.iter().map(Value::as_i64).collect::<Option<Vec<_>>>() else {
+                bail!("Invalid value for field {}", field)
+            };
+            Ok(format!("{} = ANY({})", field, bind(arr, bindings, seq)))
+        }
+        _ => match arr.first() {
+            Some(Value::Number(_)) => {
+                let Some(arr) = arr.iter().map(Value::as_f64).collect::<Option<Vec<_>>>() else {
+                    bail!("Invalid value for field {}", field)
+                };
+                let key = field_to_key(field, bindings, seq)?;
+                Ok(format!(
+                    "({})::FLOAT8 = ANY({})",
+                    key,
+                    bind(arr, bindings, seq)
+                ))
+            }
+            Some(Value::String(_)) => {
+                let Some(arr) = arr
+                    .iter()
+                    .map(|v| v.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    bail!("Invalid value for field {}", field)
+                };
+                let key = field_to_key_t(field, bindings, seq)?;
+                Ok(format!("{} = ANY({})", key, bind(arr, bindings, seq)))
+            }
+            _ => bail!("Invalid value for op IN"),
+        },
     }
-    Ok(())
 }
 
 #[cfg(test)]
@@ -261,52 +393,185 @@ mod tests {
     use super::*;
     use rest_model::Condition;
     use serde_json::json;
-    use tokio_postgres::types::ToSql;
+
+    #[test]
+    fn test_aggregate_to_sql() {
+        let mut bindings = Bindings::new();
+        let params = AggregateParams::default()
+            .filter(Condition::Eq("country".to_string(), json!("NL")))
+            .group_by("address.city")
+            .count("n")
+            .avg("age", "age");
+        let sql = aggregate_to_sql("\"mydb\".\"users\"", &params, &mut bindings, &mut 1).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT jsonb_build_object($2::TEXT, data#>$1::TEXT[], $3::TEXT, COUNT(*), \
+             $5::TEXT, AVG(CASE WHEN jsonb_typeof(data#>$4::TEXT[]) = 'number' THEN (data#>$4::TEXT[])::FLOAT8 END)) \
+             FROM \"mydb\".\"users\" WHERE data#>$6::TEXT[] = $7 \
+             GROUP BY data#>$1::TEXT[] ORDER BY data#>$1::TEXT[]"
+        );
+        assert_eq!(bindings.len(), 7);
+        assert_eq!(format!("{:?}", bindings[0]), r#"["address", "city"]"#);
+
+        let params = AggregateParams::default().sum("total", "_id");
+        assert!(aggregate_to_sql("\"mydb\".\"users\"", &params, &mut bindings, &mut 1).is_err());
+    }
+
+    #[test]
+    fn test_distinct_to_sql() {
+        let mut bindings = Bindings::new();
+        let filter = Condition::Gt("age".to_string(), json!(18));
+        let sql = distinct_to_sql(
+            "\"mydb\".\"users\"",
+            "address.country",
+            Some(&filter),
+            Some(5),
+            &mut bindings,
+            &mut 1,
+        )
+        .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT data#>$1::TEXT[] AS value, COUNT(*) FROM \"mydb\".\"users\" \
+             WHERE data#>$1::TEXT[] IS NOT NULL AND jsonb_typeof(data#>$1::TEXT[]) <> 'null' \
+             AND data#>$2::TEXT[] > $3 \
+             GROUP BY value ORDER BY COUNT(*) DESC, value LIMIT $4"
+        );
+        assert_eq!(bindings.len(), 4);
+        assert!(distinct_to_sql(
+            "\"mydb\".\"users\"",
+            "a..b",
+            None,
+            None,
+            &mut bindings,
+            &mut 1
+        )
+        .is_err());
+    }
 
     #[test]
     fn test_sort_to_sql() {
-        assert_eq!(sort_to_sql("+name").unwrap(), "data#>>'{name}' ASC");
-        assert_eq!(sort_to_sql("-age").unwrap(), "data#>>'{age}' DESC");
-        assert_eq!(sort_to_sql("+name-age").unwrap(), "data#>>'{name}' ASC, data#>>'{age}' DESC");
-        assert_eq!(sort_to_sql("").unwrap(), "_id ASC");
+        let mut bindings = Bindings::new();
+        assert_eq!(
+            sort_to_sql("+name", &[], None, &mut bindings, &mut 1).unwrap(),
+            "data#>>$1::TEXT[] ASC, _id ASC"
+        );
+        assert_eq!(
+            sort_to_sql("-age", &[], None, &mut bindings, &mut 2).unwrap(),
+            "data#>>$2::TEXT[] DESC, _id ASC"
+        );
+        assert_eq!(
+            sort_to_sql("+name-age", &[], None, &mut bindings, &mut 3).unwrap(),
+            "data#>>$3::TEXT[] ASC, data#>>$4::TEXT[] DESC, _id ASC"
+        );
+        assert_eq!(bindings.len(), 4);
+        assert_eq!(
+            sort_to_sql("", &[], None, &mut bindings, &mut 5).unwrap(),
+            "_id ASC"
+        );
+    }
+
+    #[test]
+    fn test_typed_sort_to_sql() {
+        let sort_types = [FieldSort {
+            field: "age",
+            sort_type: SortType::Number,
+        }];
+        let mut bindings = Bindings::new();
+        assert_eq!(
+            sort_to_sql("-age", &sort_types, None, &mut bindings, &mut 1).unwrap(),
+            "CASE WHEN jsonb_typeof(data#>$1::TEXT[]) = 'number' \
+             THEN (data#>$1::TEXT[])::FLOAT8 END DESC, _id ASC"
+        );
+        assert_eq!(
+            sort_to_sql(
+                "-age:text:nulls_last",
+                &sort_types,
+                None,
+                &mut bindings,
+                &mut 2
+            )
+            .unwrap(),
+            "data#>>$2::TEXT[] DESC NULLS LAST, _id ASC"
+        );
+        assert_eq!(
+            sort_to_sql("+_created_at-_id", &sort_types, None, &mut bindings, &mut 3).unwrap(),
+            "_created_at ASC, _id DESC"
+        );
+        assert_eq!(bindings.len(), 2);
+
+        let sql = sort_to_sql(
+            "+born:timestamp:nulls_first",
+            &[],
+            None,
+            &mut bindings,
+            &mut 3,
+        )
+        .unwrap();
+        assert!(sql.starts_with("CASE jsonb_typeof(data#>$3::TEXT[]) WHEN 'number'"));
+        assert!(sql.ends_with("::TIMESTAMPTZ END END ASC NULLS FIRST, _id ASC"));
+        assert_eq!(bindings.len(), 3);
+
+        assert_eq!(
+            sort_to_sql(
+                "-_rank",
+                &[],
+                Some("ts_rank(_search, q)"),
+                &mut bindings,
+                &mut 4
+            )
+            .unwrap(),
+            "ts_rank(_search, q) DESC, _id ASC"
+        );
+        assert!(sort_to_sql("-_rank", &[], None, &mut bindings, &mut 4).is_err());
+        assert!(sort_to_sql("+age:integer", &[], None, &mut bindings, &mut 4).is_err());
+        assert!(sort_to_sql("+age:nulls", &[], None, &mut bindings, &mut 4).is_err());
     }
 
     #[test]
     fn test_normal_comparison() {
         let mut seq = 1;
-        let mut bindings: Vec<Box<dyn ToSql + Sync>> = Vec::new();
+        let mut bindings = Bindings::new();
 
         let sql = normal_comparison(&mut seq, &mut bindings, "_id", "=", &json!("123")).unwrap();
         assert_eq!(sql, "_id = $1");
         assert_eq!(bindings.len(), 1);
 
-        let sql = normal_comparison(&mut seq, &mut bindings, "_created_at", ">", &json!(123456)).unwrap();
+        let sql =
+            normal_comparison(&mut seq, &mut bindings, "_created_at", ">", &json!(123456)).unwrap();
         assert_eq!(sql, "_created_at > $2");
         assert_eq!(bindings.len(), 2);
 
         let sql = normal_comparison(&mut seq, &mut bindings, "age", "<", &json!(30)).unwrap();
-        assert_eq!(sql, "data#>'{age}' < $3");
-        assert_eq!(bindings.len(), 3);
+        assert_eq!(sql, "data#>$3::TEXT[] < $4");
+        assert_eq!(bindings.len(), 4);
+
+        assert!(
+            normal_comparison(&mut seq, &mut bindings, "_created_at", ">", &json!(1.5)).is_err()
+        );
     }
 
     #[test]
     fn test_array_comparison() {
         let mut seq = 1;
-        let mut bindings: Vec<Box<dyn ToSql + Sync>> = Vec::new();
+        let mut bindings = Bindings::new();
 
-        let sql = array_comparison(&mut seq, &mut bindings, "_id", &json!(["a1", "b2", "c3"])).unwrap();
+        let sql =
+            array_comparison(&mut seq, &mut bindings, "_id", &json!(["a1", "b2", "c3"])).unwrap();
         assert_eq!(sql, "_id = ANY($1)");
         assert_eq!(bindings.len(), 1);
 
         let sql = array_comparison(&mut seq, &mut bindings, "age", &json!([20, 25, 30])).unwrap();
-        assert!(sql.contains("ANY($2)"));
-        assert_eq!(bindings.len(), 2);
+        assert_eq!(sql, "(data#>$2::TEXT[])::FLOAT8 = ANY($3)");
+        assert_eq!(bindings.len(), 3);
+
+        assert!(array_comparison(&mut seq, &mut bindings, "_id", &json!(["a", 1])).is_err());
     }
 
     #[test]
     fn test_cond_to_sql() {
         let mut seq = 1;
-        let mut bindings: Vec<Box<dyn ToSql + Sync>> = Vec::new();
+        let mut bindings = Bindings::new();
 
         let cond = Condition::Eq("_id".to_string(), json!("123"));
         let sql = cond_to_sql(&cond, &mut bindings, &mut seq).unwrap();
@@ -315,38 +580,91 @@ mod tests {
 
         let cond = Condition::In("age".to_string(), json!([25, 30, 35]));
         let sql = cond_to_sql(&cond, &mut bindings, &mut seq).unwrap();
-        assert!(sql.contains("ANY($2)"));
-        assert_eq!(bindings.len(), 2);
+        assert!(sql.contains("ANY($3)"));
+        assert_eq!(bindings.len(), 3);
 
         let cond = Condition::And(vec![
             Box::new(Condition::Gt("score".to_string(), json!(80))),
             Box::new(Condition::Lt("score".to_string(), json!(100))),
         ]);
         let sql = cond_to_sql(&cond, &mut bindings, &mut seq).unwrap();
-        assert_eq!(&sql, "(data#>'{score}' > $3 AND data#>'{score}' < $4)");
-        assert_eq!(bindings.len(), 4);
+        assert_eq!(&sql, "(data#>$4::TEXT[] > $5 AND data#>$6::TEXT[] < $7)");
+        assert_eq!(bindings.len(), 7);
     }
 
     #[test]
     fn test_regex_conditions() {
         let mut seq = 1;
-        let mut bindings: Vec<Box<dyn ToSql + Sync>> = Vec::new();
+        let mut bindings = Bindings::new();
 
         let cond = Condition::Regex("name".to_string(), json!("^J.*"));
         let sql = cond_to_sql(&cond, &mut bindings, &mut seq).unwrap();
-        assert_eq!(sql, "data#>>'{name}' ~ $1");
-        assert_eq!(bindings.len(), 1);
+        assert_eq!(sql, "data#>>$1::TEXT[] ~ $2");
+        assert_eq!(bindings.len(), 2);
 
         let cond = Condition::Regexi("name".to_string(), json!("^J.*"));
         let sql = cond_to_sql(&cond, &mut bindings, &mut seq).unwrap();
-        assert_eq!(sql, "data#>>'{name}' ~* $2");
-        assert_eq!(bindings.len(), 2);
+        assert_eq!(sql, "data#>>$3::TEXT[] ~* $4");
+        assert_eq!(bindings.len(), 4);
     }
 
     #[test]
-    fn test_invalid_chars() {
-        assert!(check_invalid_chars("valid_field").is_ok());
-        assert!(check_invalid_chars("invalid'field").is_err());
-        assert!(check_invalid_chars("invalid(field)").is_err());
+    fn test_identifiers() {
+        assert_eq!(quote_ident("users").unwrap(), "\"users\"");
+        assert_eq!(quote_ident("Users").unwrap(), "\"users\"");
+        assert_eq!(
+            table_ref("mydb.tenant_a", "users").unwrap(),
+            "\"mydb\".\"tenant_a\".\"users\""
+        );
+        for name in [
+            "",
+            "users\"",
+            "users; DROP TABLE users",
+            "a.b",
+            "us'ers",
+            "1users",
+        ] {
+            assert!(quote_ident(name).is_err(), "{:?}", name);
+        }
+        assert!(table_ref("mydb", "users\"; DROP TABLE users;--").is_err());
+        assert!(table_ref("mydb\"; --", "users").is_err());
+        assert!(table_ref("mydb..x", "users").is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_injection_is_bound() {
+        let attempts = [
+            "name'); DROP TABLE users;--",
+            "name}' OR '1'='1",
+            "a\"b",
+            "a,b}",
+            "$1",
+        ];
+        for field in attempts {
+            let mut seq = 1;
+            let mut bindings = Bindings::new();
+            let cond = Condition::Eq(field.to_string(), json!("x' OR '1'='1"));
+            let sql = cond_to_sql(&cond, &mut bindings, &mut seq).unwrap();
+            assert_eq!(sql, "data#>$1::TEXT[] = $2");
+            assert_eq!(format!("{:?}", bindings[0]), format!("{:?}", [field]));
+
+            let sql = sort_to_sql(field, &[], None, &mut bindings, &mut seq).unwrap();
+            assert!(!sql.contains(field), "{}", sql);
+        }
+
+        let mut bindings = Bindings::new();
+        let cond = Condition::Eq("_id".to_string(), json!("x' OR '1'='1"));
+        assert_eq!(
+            cond_to_sql(&cond, &mut bindings, &mut 1).unwrap(),
+            "_id = $1"
+        );
+        for field in ["", ".", "a.", ".a", "a..b"] {
+            let cond = Condition::Eq(field.to_string(), json!(1));
+            assert!(
+                cond_to_sql(&cond, &mut bindings, &mut 1).is_err(),
+                "{:?}",
+                field
+            );
+        }
+    }
+}
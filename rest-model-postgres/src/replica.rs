@@ -0,0 +1,123 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+use tracing::warn;
+
+use crate::{Db, PgConnection, PgPool};
+
+/// How long a read waits for a replica connection before trying the next one,
+/// unless `DbBuilder::connection_timeout` is set.
+pub(crate) const REPLICA_CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often every replica is probed, so unhealthy ones are skipped and
+/// recovered ones are used again.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The read replicas of a `Db`, shared by its clones.
+#[derive(Debug, Default)]
+pub struct Replicas {
+    pub replicas: Vec<Replica>,
+    /// Round-robin position of the next read.
+    next: AtomicUsize,
+}
+
+#[derive(Debug)]
+pub struct Replica {
+    pool: PgPool,
+    healthy: AtomicBool,
+}
+
+impl Replica {
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    /// False from a failed checkout or health check until the next successful health check.
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+}
+
+impl Replicas {
+    pub(crate) fn new(pools: Vec<PgPool>) -> Self {
+        Self {
+            replicas: pools
+                .into_iter()
+                .map(|pool| Replica {
+                    pool,
+                    healthy: AtomicBool::new(true),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Healthy replicas in round-robin order.
+    fn candidates(&self) -> impl Iterator<Item = &Replica> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let len = self.replicas.len();
+        (0..len)
+            .map(move |i| &self.replicas[(start + i) % len])
+            .filter(|replica| replica.is_healthy())
+    }
+
+    /// Probes every replica until the `Db` owning them is dropped.
+    pub(crate) fn spawn_health_check(replicas: &Arc<Self>) {
+        if replicas.replicas.is_empty() {
+            return;
+        }
+        let replicas = Arc::downgrade(replicas);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(replicas) = Weak::upgrade(&replicas) else {
+                    return;
+                };
+                for replica in &replicas.replicas {
+                    let healthy = match replica.pool.get().await {
+                        Ok(conn) => conn.simple_query("SELECT 1").await.is_ok(),
+                        Err(_) => false,
+                    };
+                    replica.healthy.store(healthy, Ordering::Relaxed);
+                }
+            }
+        });
+    }
+}
+
+impl Db {
+    /// A connection for a read: from the next healthy replica, falling back
+    /// to the other replicas and then the primary.
+    pub(crate) async fn read_conn(&self) -> Result<PgConnection<'_>> {
+        if !self.read_from_primary {
+            for replica in self.replicas.candidates() {
+                match replica.pool.get().await {
+                    Ok(conn) => return Ok(conn),
+                    Err(e) => {
+                        warn!("replica unavailable, reading elsewhere: {}", e);
+                        replica.healthy.store(false, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+        Ok(self.pool.get().await?)
+    }
+
+    /// The pool a long-lived read such as `stream` checks out from, chosen
+    /// up front by the same rules as `read_conn`.
+    pub(crate) fn read_pool(&self) -> &PgPool {
+        if self.read_from_primary {
+            return &self.pool;
+        }
+        match self.replicas.candidates().next() {
+            Some(replica) => &replica.pool,
+            None => &self.pool,
+        }
+    }
+}
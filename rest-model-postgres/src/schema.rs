@@ -0,0 +1,179 @@
+use serde_json::Value;
+
+/// Compiles the JSON Schema subset emitted by `#[rest_model]` (`type`, `minimum`,
+/// `properties`, `required`, `items`, `additionalProperties`, `anyOf`) into a SQL
+/// boolean over the `data` column, usable in a CHECK constraint.
+///
+/// Element schemas of arrays and maps are checked through `jsonpath` for their
+/// type and minimum only; deeper nesting inside them is not enforced.
+pub fn schema_to_check(schema: &Value) -> String {
+    check(schema, "data").unwrap_or_else(|| "TRUE".to_string())
+}
+
+fn check(schema: &Value, expr: &str) -> Option<String> {
+    let mut checks = vec![];
+
+    if let Some(types) = types(schema) {
+        let types = types
+            .iter()
+            .map(|t| type_check(t, expr))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        checks.push(format!("({})", types));
+    }
+
+    if let Some(minimum) = schema.get("minimum").and_then(|m| m.as_f64()) {
+        checks.push(format!(
+            "(CASE WHEN jsonb_typeof({e}) = 'number' THEN ({e})::numeric >= {m} ELSE TRUE END)",
+            e = expr,
+            m = minimum
+        ));
+    }
+
+    if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+        for key in required.iter().filter_map(|k| k.as_str()) {
+            checks.push(format!("{} ? {}", expr, literal(key)));
+        }
+    }
+
+    if let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) {
+        for (key, property) in properties {
+            let child = format!("({}->{})", expr, literal(key));
+            if let Some(c) = check(property, &child) {
+                checks.push(format!("({} IS NULL OR {})", child, c));
+            }
+        }
+    }
+
+    if let Some(predicate) = schema.get("items").and_then(path_predicate) {
+        checks.push(format!(
+            "(CASE WHEN jsonb_typeof({e}) = 'array' THEN NOT jsonb_path_exists({e}, {p}) ELSE TRUE END)",
+            e = expr,
+            p = literal(&format!("strict $[*] ? (!({}))", predicate))
+        ));
+    }
+
+    if let Some(predicate) = schema.get("additionalProperties").and_then(path_predicate) {
+        checks.push(format!(
+            "(CASE WHEN jsonb_typeof({e}) = 'object' THEN NOT jsonb_path_exists({e}, {p}) ELSE TRUE END)",
+            e = expr,
+            p = literal(&format!("strict $.* ? (!({}))", predicate))
+        ));
+    }
+
+    if let Some(branches) = schema.get("anyOf").and_then(|a| a.as_array()) {
+        let branches = branches
+            .iter()
+            .map(|b| check(b, expr))
+            .collect::<Option<Vec<_>>>();
+        // a branch without constraints accepts anything, and so does the union
+        if let Some(branches) = branches {
+            checks.push(format!("({})", branches.join(" OR ")));
+        }
+    }
+
+    if checks.is_empty() {
+        None
+    } else {
+        Some(checks.join(" AND "))
+    }
+}
+
+fn types(schema: &Value) -> Option<Vec<&str>> {
+    match schema.get("type")? {
+        Value::String(t) => Some(vec![t.as_str()]),
+        Value::Array(ts) => Some(ts.iter().filter_map(|t| t.as_str()).collect()),
+        _ => None,
+    }
+}
+
+fn type_check(t: &str, expr: &str) -> String {
+    match t {
+        "integer" => format!(
+            "(CASE WHEN jsonb_typeof({e}) = 'number' THEN ({e})::numeric = trunc(({e})::numeric) ELSE FALSE END)",
+            e = expr
+        ),
+        _ => format!("jsonb_typeof({}) = {}", expr, literal(t)),
+    }
+}
+
+/// A `jsonpath` filter predicate on `@` for an element schema, if it constrains anything.
+fn path_predicate(schema: &Value) -> Option<String> {
+    let mut predicates = vec![];
+    if let Some(types) = types(schema) {
+        let types = types
+            .iter()
+            .map(|t| match *t {
+                "integer" => "(@.type() == \"number\" && @.floor() == @)".to_string(),
+                t => format!("@.type() == \"{}\"", t),
+            })
+            .collect::<Vec<_>>()
+            .join(" || ");
+        predicates.push(format!("({})", types));
+    }
+    if let Some(minimum) = schema.get("minimum").and_then(|m| m.as_f64()) {
+        predicates.push(format!("(@.type() != \"number\" || @ >= {})", minimum));
+    }
+    if let Some(branches) = schema.get("anyOf").and_then(|a| a.as_array()) {
+        let branches = branches
+            .iter()
+            .map(path_predicate)
+            .collect::<Option<Vec<_>>>();
+        if let Some(branches) = branches {
+            predicates.push(format!("({})", branches.join(" || ")));
+        }
+    }
+    if predicates.is_empty() {
+        None
+    } else {
+        Some(predicates.join(" && "))
+    }
+}
+
+/// A SQL string literal.
+pub(crate) fn literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_schema_to_check() {
+        assert_eq!(schema_to_check(&json!({})), "TRUE");
+        assert_eq!(
+            schema_to_check(&json!({
+                "type": "object",
+                "properties": { "name": { "type": "string" } },
+                "required": ["name"],
+            })),
+            "(jsonb_typeof(data) = 'object') AND data ? 'name' AND ((data->'name') IS NULL OR (jsonb_typeof((data->'name')) = 'string'))"
+        );
+    }
+
+    #[test]
+    fn test_optional_and_items() {
+        let sql = schema_to_check(&json!({
+            "type": "object",
+            "properties": {
+                "tags": {
+                    "anyOf": [
+                        { "type": "array", "items": { "type": "string" } },
+                        { "type": "null" },
+                    ],
+                },
+            },
+            "required": [],
+        }));
+        assert!(sql.contains("jsonb_typeof((data->'tags')) = 'null'"));
+        assert!(sql.contains("strict $[*] ? (!((@.type() == \"string\")))"));
+    }
+
+    #[test]
+    fn test_quotes_are_escaped() {
+        let sql = schema_to_check(&json!({ "required": ["it's"] }));
+        assert_eq!(sql, "data ? 'it''s'");
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::Result;
+use rest_model::{check_identifier, SearchConfig};
+
+use crate::{bind, quote_ident, schema::literal, Bindings, Db};
+
+/// `ts_headline` options wrapping each match in `<mark>`; the text around
+/// them is HTML-escaped first, see `escape_html`.
+const HIGHLIGHT_OPTIONS: &str = "'StartSel=<mark>, StopSel=</mark>'";
+
+/// The expression of the generated `_search` column: the words of every
+/// string in each field, weighted `A`, `B`, `C` and then `D` by position.
+fn search_vector(search: &SearchConfig) -> Result<String> {
+    check_identifier(search.language)?;
+    let language = format!("{}::REGCONFIG", literal(search.language));
+    Ok(search
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            format!(
+                "setweight(jsonb_to_tsvector({}, COALESCE(data->{}, 'null'), '[\"string\"]'), '{}')",
+                language,
+                literal(field),
+                ["A", "B", "C"].get(i).unwrap_or(&"D")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" || "))
+}
+
+/// `json` with `&`, `<` and `>` HTML-escaped inside its strings; none of them
+/// is JSON syntax, so the result is still the same shape of JSON.
+fn escape_html(json: &str) -> String {
+    format!(
+        "replace(replace(replace(({})::TEXT, '&', '&amp;'), '<', '&lt;'), '>', '&gt;')::JSONB",
+        json
+    )
+}
+
+impl Db {
+    /// Adds the `_search` column and its GIN index to `table`, unless the
+    /// column's comment shows they are already built from the same `search`.
+    ///
+    /// Adding a stored generated column rewrites the whole table under an
+    /// `ACCESS EXCLUSIVE` lock.
+    pub(crate) async fn install_search_column(
+        &self,
+        table: &str,
+        table_name: &str,
+        search: &SearchConfig,
+    ) -> Result<()> {
+        let vector = search_vector(search)?;
+        let conn = self.pool.get().await?;
+        let installed: Option<String> = conn
+            .query_opt(
+                "SELECT col_description(attrelid, attnum) FROM pg_attribute
+                WHERE attrelid = to_regclass($1) AND attname = '_search' AND NOT attisdropped",
+                &[&table],
+            )
+            .await?
+            .and_then(|row| row.get(0));
+        if installed.as_deref() == Some(vector.as_str()) {
+            return Ok(());
+        }
+        // the index goes with the old column
+        conn.batch_execute(&format!(
+            "ALTER TABLE {table} DROP COLUMN IF EXISTS _search;
+            ALTER TABLE {table} ADD COLUMN _search TSVECTOR GENERATED ALWAYS AS ({vector}) STORED;
+            CREATE INDEX {index} ON {table} USING GIN (_search);
+            COMMENT ON COLUMN {table}._search IS {comment};",
+            index = quote_ident(&format!("{}_search", table_name))?,
+            comment = literal(&vector),
+        ))
+        .await?;
+        Ok(())
+    }
+}
+
+/// The SQL of a `PaginationParams::q` search, bound once and reused by the
+/// filter, `_rank` and highlights.
+pub struct SearchSql {
+    language: String,
+    query: String,
+}
+
+impl SearchSql {
+    pub fn new(q: &str, search: &SearchConfig, bindings: &mut Bindings, seq: &mut u32) -> Self {
+        let language = format!(
+            "{}::TEXT::REGCONFIG",
+            bind(search.language.to_string(), bindings, seq)
+        );
+        let query = format!(
+            "websearch_to_tsquery({}, {})",
+            language,
+            bind(q.to_string(), bindings, seq)
+        );
+        Self { language, query }
+    }
+
+    pub fn filter(&self) -> String {
+        format!("_search @@ {}", self.query)
+    }
+
+    /// Relevance, for the `_rank` sort key.
+    pub fn rank(&self) -> String {
+        format!("ts_rank(_search, {})", self.query)
+    }
+
+    /// A `jsonb` object of the highlighted matches by field, without the
+    /// fields that don't match. Only the `<mark>` tags are markup, so the
+    /// strings are safe to insert as HTML.
+    pub fn highlights(
+        &self,
+        search: &SearchConfig,
+        bindings: &mut Bindings,
+        seq: &mut u32,
+    ) -> String {
+        let pairs = search
+            .fields
+            .iter()
+            .map(|field| {
+                let field = bind(field.to_string(), bindings, seq);
+                format!(
+                    "{f}::TEXT, CASE WHEN jsonb_to_tsvector({l}, data->{f}::TEXT, '[\"string\"]') @@ {q} \
+                     THEN ts_headline({l}, {e}, {q}, {o}) END",
+                    f = field,
+                    e = escape_html(&format!("data->{}::TEXT", field)),
+                    l = self.language,
+                    q = self.query,
+                    o = HIGHLIGHT_OPTIONS
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "jsonb_strip_nulls(jsonb_build_object({}))",
+            pairs.join(", ")
+        )
+    }
+}
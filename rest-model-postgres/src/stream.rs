@@ -0,0 +1,103 @@
+use anyhow::Result;
+use futures_util::{stream, Stream, TryStreamExt};
+use rest_model::{Condition, Doc, RestModel};
+use tokio_postgres::{types::ToSql, Client, Row};
+use tracing::debug;
+
+use crate::{cond_to_sql, row_to_doc, sort_to_sql, table_ref, Db, PgPool};
+
+/// Rows pulled from the cursor per round trip.
+const FETCH_SIZE: u32 = 500;
+
+impl Db {
+    /// Every document of `T` matching `filter`, ordered by `sort` (`_id` by
+    /// default), read through a server-side cursor so memory stays bounded by
+    /// one batch however large the table is.
+    ///
+    /// The stream opens its own connection outside the pool and holds it,
+    /// inside a transaction, until it ends or is dropped. Dropping it closes
+    /// the connection, so an abandoned transaction can never be handed to the
+    /// pool's next borrower.
+    pub fn stream<T: RestModel>(
+        &self,
+        filter: Option<&Condition>,
+        sort: Option<&str>,
+    ) -> Result<impl Stream<Item = Result<Doc<T>>> + Send + 'static> {
+        let (db_name, table_name) = self
+            .namespace
+            .resolve(T::get_db_name(), T::get_table_name())?;
+        let mut bindings = vec![];
+        let mut seq = 1;
+        let where_sql = match filter {
+            Some(filter) => format!("WHERE {}", cond_to_sql(filter, &mut bindings, &mut seq)?),
+            None => "".to_string(),
+        };
+        let order_sql = match sort {
+            Some(sort_expr) => sort_to_sql(
+                sort_expr,
+                T::get_sort_types(),
+                None,
+                &mut bindings,
+                &mut seq,
+            )?,
+            None => "_id ASC".to_string(),
+        };
+        let sql = format!(
+            "DECLARE rest_model_stream NO SCROLL CURSOR FOR SELECT * FROM {} {} ORDER BY {}",
+            table_ref(&db_name, &table_name)?,
+            where_sql,
+            order_sql
+        );
+        let cursor = Cursor {
+            pool: self.read_pool().clone(),
+            declare: Some((sql, bindings)),
+            conn: None,
+        };
+        Ok(stream::try_unfold(cursor, |mut cursor| async move {
+            Ok::<_, anyhow::Error>(cursor.fetch().await?.map(|rows| (rows, cursor)))
+        })
+        .map_ok(|rows| stream::iter(rows.iter().map(row_to_doc).collect::<Vec<_>>()))
+        .try_flatten())
+    }
+}
+
+type Declare = (String, Vec<Box<dyn ToSql + Sync + Send>>);
+
+struct Cursor {
+    pool: PgPool,
+    /// Run on the first `fetch`, so nothing is checked out until the stream is polled.
+    declare: Option<Declare>,
+    /// Set while the transaction holding the cursor is open; never returned
+    /// to the pool, so dropping it mid-stream closes the connection and the
+    /// server rolls the transaction back.
+    conn: Option<Client>,
+}
+
+impl Cursor {
+    /// The next batch of rows, or `None` once the cursor is exhausted.
+    async fn fetch(&mut self) -> Result<Option<Vec<Row>>> {
+        if let Some((sql, bindings)) = self.declare.take() {
+            let conn = self.pool.dedicated_connection().await?;
+            conn.batch_execute("BEGIN").await?;
+            let conn = self.conn.insert(conn);
+            debug!("{}", sql);
+            let args_refs: Vec<&(dyn ToSql + Sync)> = bindings
+                .iter()
+                .map(|x| x.as_ref() as &(dyn ToSql + Sync))
+                .collect();
+            conn.execute(&sql, &args_refs).await?;
+        }
+        let Some(conn) = &self.conn else {
+            return Ok(None);
+        };
+        let rows = conn
+            .query(&format!("FETCH {} FROM rest_model_stream", FETCH_SIZE), &[])
+            .await?;
+        if rows.is_empty() {
+            conn.batch_execute("COMMIT").await?;
+            self.conn = None;
+            return Ok(None);
+        }
+        Ok(Some(rows))
+    }
+}
@@ -0,0 +1,61 @@
+use bb8_postgres::bb8::RunError;
+use rest_model::retry::Transient;
+use tokio_postgres::error::SqlState;
+
+/// Classifies the errors `Db` returns for `DbClient::transient`.
+pub fn transient(error: &anyhow::Error) -> Option<Transient> {
+    if let Some(error) = error.downcast_ref::<RunError<tokio_postgres::Error>>() {
+        // failed before any statement ran, so nothing was applied; only a
+        // connect, I/O or timeout failure is worth retrying, not bad
+        // credentials, an unknown database or invalid configuration
+        return match error {
+            RunError::User(error) => classify(error).map(|_| Transient::NotApplied),
+            RunError::TimedOut => Some(Transient::NotApplied),
+        };
+    }
+    classify(error.downcast_ref::<tokio_postgres::Error>()?)
+}
+
+/// Classifies the errors `Db` returns for `DbClient::data_error`: the
+/// documents failed to serialize, or the server rejected a value (SQLSTATE
+/// class 22) or a constraint (class 23).
+pub fn data_error(error: &anyhow::Error) -> bool {
+    if error.is::<serde_json::Error>() {
+        return true;
+    }
+    error
+        .downcast_ref::<tokio_postgres::Error>()
+        .and_then(tokio_postgres::Error::code)
+        .is_some_and(|code| code.code().starts_with("22") || code.code().starts_with("23"))
+}
+
+fn classify(error: &tokio_postgres::Error) -> Option<Transient> {
+    match error.code() {
+        // the transaction was rolled back, or the server refused to start it
+        Some(code)
+            if code == &SqlState::T_R_SERIALIZATION_FAILURE
+                || code == &SqlState::T_R_DEADLOCK_DETECTED
+                || code == &SqlState::CANNOT_CONNECT_NOW =>
+        {
+            Some(Transient::NotApplied)
+        }
+        // connection exceptions and server shutdown
+        Some(code)
+            if code.code().starts_with("08")
+                || code == &SqlState::ADMIN_SHUTDOWN
+                || code == &SqlState::CRASH_SHUTDOWN =>
+        {
+            Some(Transient::MaybeApplied)
+        }
+        Some(_) => None,
+        None if error.is_closed() => Some(Transient::MaybeApplied),
+        // no SQLSTATE and still open: an I/O error on the socket, including
+        // refused and timed out connects
+        None if std::error::Error::source(error)
+            .is_some_and(|source| source.is::<std::io::Error>()) =>
+        {
+            Some(Transient::MaybeApplied)
+        }
+        None => None,
+    }
+}
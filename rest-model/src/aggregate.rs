@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::Condition;
+
+/// Summarizes one field (or counts rows) within each group.
+///
+/// Numeric accumulators ignore documents where the field is missing or not a
+/// JSON number.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Accumulator {
+    Count,
+    Sum(String),
+    Avg(String),
+    Min(String),
+    Max(String),
+}
+
+impl Accumulator {
+    /// The field path summarized, if any.
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            Accumulator::Count => None,
+            Accumulator::Sum(field)
+            | Accumulator::Avg(field)
+            | Accumulator::Min(field)
+            | Accumulator::Max(field) => Some(field),
+        }
+    }
+}
+
+/// A grouping query, e.g. "orders per status":
+///
+/// ```
+/// use rest_model::AggregateParams;
+///
+/// let params = AggregateParams::default()
+///     .group_by("status")
+///     .count("orders")
+///     .sum("total", "amount");
+/// ```
+///
+/// Each result row is a JSON object holding the `group_by` paths and the
+/// accumulator outputs by name, e.g. `{"status": "paid", "orders": 3, "total": 42.5}`.
+/// Without `group_by` there is exactly one row covering every matching document.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AggregateParams {
+    pub filter: Option<Condition>,
+    #[serde(default)]
+    pub group_by: Vec<String>,
+    /// `(output name, accumulator)` pairs.
+    #[serde(default)]
+    pub accumulators: Vec<(String, Accumulator)>,
+}
+
+impl AggregateParams {
+    pub fn filter(mut self, filter: Condition) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn group_by(mut self, field: &str) -> Self {
+        self.group_by.push(field.to_string());
+        self
+    }
+
+    pub fn accumulate(mut self, name: &str, accumulator: Accumulator) -> Self {
+        self.accumulators.push((name.to_string(), accumulator));
+        self
+    }
+
+    pub fn count(self, name: &str) -> Self {
+        self.accumulate(name, Accumulator::Count)
+    }
+
+    pub fn sum(self, name: &str, field: &str) -> Self {
+        self.accumulate(name, Accumulator::Sum(field.to_string()))
+    }
+
+    pub fn avg(self, name: &str, field: &str) -> Self {
+        self.accumulate(name, Accumulator::Avg(field.to_string()))
+    }
+
+    pub fn min(self, name: &str, field: &str) -> Self {
+        self.accumulate(name, Accumulator::Min(field.to_string()))
+    }
+
+    pub fn max(self, name: &str, field: &str) -> Self {
+        self.accumulate(name, Accumulator::Max(field.to_string()))
+    }
+
+    /// Every field path read by this query.
+    pub fn fields(&self) -> Vec<&str> {
+        self.filter
+            .iter()
+            .flat_map(|f| f.fields())
+            .chain(self.group_by.iter().map(|f| f.as_str()))
+            .chain(self.accumulators.iter().filter_map(|(_, a)| a.field()))
+            .collect()
+    }
+}
+
+/// A distinct value of a field and how many documents hold it, e.g. for
+/// facet counts next to a filter dropdown.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Facet<V = Value> {
+    pub value: V,
+    pub count: u32,
+}
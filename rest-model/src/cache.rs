@@ -0,0 +1,499 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    pagination::PaginationParams, retry::Transient, AggregateParams, Condition, DbClient,
+    DeleteParams, DeleteResult, Doc, Facet, InsertResult, OnConflict, PaginationResult,
+    PatchParams, ReplaceResult, RestModel, Returning, SearchConfig, UpdateResult, UpsertResult,
+    WriteGuard,
+};
+
+/// `(db_name, table_name, id or JSON-encoded PaginationParams)`.
+type Key = (String, String, String);
+
+struct Entry {
+    value: Value,
+    expires_at: Instant,
+}
+
+struct Entries {
+    by_id: LruCache<Key, Entry>,
+    pages: Option<LruCache<Key, Entry>>,
+    /// Bumped by every invalidation, so a read that raced a write doesn't
+    /// cache what it fetched before the write.
+    generation: u64,
+}
+
+/// Hit/miss counters of a `CachedDb`, cumulative since it was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub page_hits: u64,
+    pub page_misses: u64,
+    /// Documents currently cached by id.
+    pub entries: usize,
+    /// Pages currently cached.
+    pub page_entries: usize,
+}
+
+/// Wraps any `DbClient` with a bounded in-process LRU cache of `select_by_id`
+/// results, each kept for at most `ttl`:
+///
+/// ```ignore
+/// let client = CachedDb::new(Db::try_new(uri).await?, 10_000, Duration::from_secs(60))
+///     .with_page_cache(1_000);
+/// ```
+///
+/// Writes through the wrapper invalidate what they may have changed: the
+/// written ids for `upsert`/`insert`/`replace`/`copy_in`, the whole table for
+/// `update`/`delete`, plus the tables a delete cascaded into. Writes that
+/// bypass the wrapper are only picked up once entries expire.
+pub struct CachedDb<D> {
+    inner: D,
+    entries: Mutex<Entries>,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    page_hits: AtomicU64,
+    page_misses: AtomicU64,
+}
+
+impl<D> CachedDb<D> {
+    /// Caches up to `capacity` documents by id.
+    pub fn new(inner: D, capacity: usize, ttl: Duration) -> Self {
+        Self {
+            inner,
+            entries: Mutex::new(Entries {
+                by_id: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)),
+                pages: None,
+                generation: 0,
+            }),
+            ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            page_hits: AtomicU64::new(0),
+            page_misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Also caches up to `capacity` `paginate` results, keyed by their
+    /// `PaginationParams`. Pages asking to `expand` are never cached, since
+    /// writes to the referenced tables wouldn't invalidate them.
+    pub fn with_page_cache(self, capacity: usize) -> Self {
+        self.lock().pages = Some(LruCache::new(
+            NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN),
+        ));
+        self
+    }
+
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.lock();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            page_hits: self.page_hits.load(Ordering::Relaxed),
+            page_misses: self.page_misses.load(Ordering::Relaxed),
+            entries: entries.by_id.len(),
+            page_entries: entries.pages.as_ref().map_or(0, |pages| pages.len()),
+        }
+    }
+
+    /// Drops every cached document and page.
+    pub fn clear(&self) {
+        self.invalidate(|_, _| true);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn generation(&self) -> u64 {
+        self.lock().generation
+    }
+
+    fn get_by_id(&self, key: &Key) -> Option<Value> {
+        let value = lookup(&mut self.lock().by_id, key);
+        let counter = match value {
+            Some(_) => &self.hits,
+            None => &self.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    fn put_by_id(&self, generation: u64, key: Key, value: Value) {
+        let mut entries = self.lock();
+        if entries.generation == generation {
+            let expires_at = Instant::now() + self.ttl;
+            entries.by_id.put(key, Entry { value, expires_at });
+        }
+    }
+
+    fn get_page(&self, key: &Key) -> Option<Value> {
+        let value = lookup(self.lock().pages.as_mut()?, key);
+        let counter = match value {
+            Some(_) => &self.page_hits,
+            None => &self.page_misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    fn put_page(&self, generation: u64, key: Key, value: Value) {
+        let mut entries = self.lock();
+        if entries.generation == generation {
+            let expires_at = Instant::now() + self.ttl;
+            if let Some(pages) = entries.pages.as_mut() {
+                pages.put(key, Entry { value, expires_at });
+            }
+        }
+    }
+
+    /// Drops the by-id entries `(db_name, table_name, id)` and all pages of
+    /// tables `(db_name, table_name)` matching `stale`.
+    fn invalidate(&self, stale: impl Fn(&str, &str) -> bool) {
+        let mut entries = self.lock();
+        entries.generation += 1;
+        remove_where(&mut entries.by_id, |(db, table, _)| stale(db, table));
+        if let Some(pages) = entries.pages.as_mut() {
+            remove_where(pages, |(db, table, _)| stale(db, table));
+        }
+    }
+
+    /// Drops the given ids and every page of the table.
+    fn invalidate_ids<'a>(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        ids: impl Iterator<Item = &'a str>,
+    ) {
+        let mut entries = self.lock();
+        entries.generation += 1;
+        for id in ids {
+            entries.by_id.pop(&key(db_name, table_name, id));
+        }
+        if let Some(pages) = entries.pages.as_mut() {
+            remove_where(pages, |(db, table, _)| db == db_name && table == table_name);
+        }
+    }
+
+    fn invalidate_table(&self, db_name: &str, table_name: &str) {
+        self.invalidate(|db, table| db == db_name && table == table_name);
+    }
+}
+
+fn key(db_name: &str, table_name: &str, id: &str) -> Key {
+    (db_name.to_string(), table_name.to_string(), id.to_string())
+}
+
+fn lookup(cache: &mut LruCache<Key, Entry>, key: &Key) -> Option<Value> {
+    match cache.get(key) {
+        Some(entry) if entry.expires_at > Instant::now() => Some(entry.value.clone()),
+        Some(_) => {
+            cache.pop(key);
+            None
+        }
+        None => None,
+    }
+}
+
+fn remove_where(cache: &mut LruCache<Key, Entry>, stale: impl Fn(&Key) -> bool) {
+    let keys = cache
+        .iter()
+        .map(|(key, _)| key)
+        .filter(|key| stale(key))
+        .cloned()
+        .collect::<Vec<_>>();
+    for key in keys {
+        cache.pop(&key);
+    }
+}
+
+impl<T, D> DbClient<T> for CachedDb<D>
+where
+    T: RestModel,
+    D: DbClient<T>,
+{
+    fn generate_id(&self) -> String {
+        self.inner.generate_id()
+    }
+
+    fn resolve(&self, db_name: &str, table_name: &str) -> Result<(String, String), Error> {
+        self.inner.resolve(db_name, table_name)
+    }
+
+    fn transient(&self, error: &Error) -> Option<Transient> {
+        self.inner.transient(error)
+    }
+
+    fn data_error(&self, error: &Error) -> bool {
+        self.inner.data_error(error)
+    }
+
+    async fn init(&self, db_name: &str, table_name: &str) -> Result<(), Error> {
+        self.inner.init(db_name, table_name).await
+    }
+
+    async fn install_schema(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        schema: &Value,
+    ) -> Result<(), Error> {
+        self.inner.install_schema(db_name, table_name, schema).await
+    }
+
+    async fn install_search(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        search: &SearchConfig,
+    ) -> Result<(), Error> {
+        self.inner.install_search(db_name, table_name, search).await
+    }
+
+    async fn select_by_id(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        id: &str,
+    ) -> Result<Doc<T>, Error> {
+        let key = key(db_name, table_name, id);
+        if let Some(doc) = self.get_by_id(&key) {
+            return Ok(serde_json::from_value(doc)?);
+        }
+        let generation = self.generation();
+        let doc = self.inner.select_by_id(db_name, table_name, id).await?;
+        self.put_by_id(generation, key, serde_json::to_value(&doc)?);
+        Ok(doc)
+    }
+
+    async fn select_by_ids(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        ids: &[&str],
+    ) -> Result<Vec<Doc<T>>, Error> {
+        let mut docs = vec![];
+        let mut missing = vec![];
+        for id in ids {
+            match self.get_by_id(&key(db_name, table_name, id)) {
+                Some(doc) => docs.push(serde_json::from_value(doc)?),
+                None => missing.push(*id),
+            }
+        }
+        let generation = self.generation();
+        for doc in self
+            .inner
+            .select_by_ids(db_name, table_name, &missing)
+            .await?
+        {
+            let key = key(db_name, table_name, &doc._id);
+            self.put_by_id(generation, key, serde_json::to_value(&doc)?);
+            docs.push(doc);
+        }
+        Ok(docs)
+    }
+
+    async fn expand(&self, docs: &mut [Doc<T>], fields: &[&str]) -> Result<(), Error> {
+        self.inner.expand(docs, fields).await
+    }
+
+    async fn paginate(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        pagination_params: &PaginationParams,
+    ) -> Result<PaginationResult<T>, Error> {
+        if pagination_params.expand.is_some() {
+            return self
+                .inner
+                .paginate(db_name, table_name, pagination_params)
+                .await;
+        }
+        let key = key(
+            db_name,
+            table_name,
+            &serde_json::to_string(pagination_params)?,
+        );
+        if let Some(page) = self.get_page(&key) {
+            return Ok(serde_json::from_value(page)?);
+        }
+        let generation = self.generation();
+        let page = self
+            .inner
+            .paginate(db_name, table_name, pagination_params)
+            .await?;
+        self.put_page(generation, key, serde_json::to_value(&page)?);
+        Ok(page)
+    }
+
+    async fn aggregate(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        params: &AggregateParams,
+    ) -> Result<Vec<Value>, Error> {
+        self.inner.aggregate(db_name, table_name, params).await
+    }
+
+    async fn distinct(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        field: &str,
+        filter: Option<&Condition>,
+        limit: Option<u32>,
+    ) -> Result<Vec<Facet>, Error> {
+        self.inner
+            .distinct(db_name, table_name, field, filter, limit)
+            .await
+    }
+
+    async fn upsert(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        returning: Returning,
+    ) -> Result<UpsertResult<T>, Error> {
+        let result = self
+            .inner
+            .upsert(db_name, table_name, items, returning)
+            .await;
+        self.invalidate_ids(
+            db_name,
+            table_name,
+            items.iter().map(|doc| doc._id.as_str()),
+        );
+        result
+    }
+
+    async fn insert(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        on_conflict: OnConflict,
+    ) -> Result<InsertResult, Error> {
+        let result = self
+            .inner
+            .insert(db_name, table_name, items, on_conflict)
+            .await;
+        self.invalidate_ids(
+            db_name,
+            table_name,
+            items.iter().map(|doc| doc._id.as_str()),
+        );
+        result
+    }
+
+    async fn replace(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+    ) -> Result<ReplaceResult, Error> {
+        let result = self.inner.replace(db_name, table_name, items).await;
+        self.invalidate_ids(
+            db_name,
+            table_name,
+            items.iter().map(|doc| doc._id.as_str()),
+        );
+        result
+    }
+
+    async fn upsert_guarded(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        guard: &WriteGuard,
+    ) -> Result<UpsertResult<T>, Error> {
+        let result = self
+            .inner
+            .upsert_guarded(db_name, table_name, items, guard)
+            .await;
+        self.invalidate_ids(
+            db_name,
+            table_name,
+            items.iter().map(|doc| doc._id.as_str()),
+        );
+        result
+    }
+
+    async fn copy_in(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+    ) -> Result<u32, Error> {
+        let result = self.inner.copy_in(db_name, table_name, items).await;
+        self.invalidate_ids(
+            db_name,
+            table_name,
+            items.iter().map(|doc| doc._id.as_str()),
+        );
+        result
+    }
+
+    async fn update(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        params: &PatchParams,
+        returning: Returning,
+    ) -> Result<UpdateResult<T>, Error> {
+        let result = self
+            .inner
+            .update(db_name, table_name, params, returning)
+            .await;
+        self.invalidate_table(db_name, table_name);
+        result
+    }
+
+    async fn delete(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        filter: &DeleteParams,
+        returning: Returning,
+    ) -> Result<DeleteResult<T>, Error> {
+        let result = self
+            .inner
+            .delete(db_name, table_name, filter, returning)
+            .await;
+        self.invalidate_table(db_name, table_name);
+        if let Ok(result) = &result {
+            // Cascades report the models' own table names: resolve them into
+            // this client's namespace, and drop them in every db as theirs isn't reported.
+            let tables = result
+                .cascaded
+                .keys()
+                .chain(result.nullified.keys())
+                .map(|table| self.inner.resolve(db_name, table).map(|(_, table)| table))
+                .collect::<Result<Vec<_>, _>>();
+            match tables {
+                Ok(tables) => self.invalidate(|_, table| tables.iter().any(|t| t == table)),
+                Err(_) => self.clear(),
+            }
+        }
+        result
+    }
+}
@@ -1,8 +1,13 @@
-use anyhow::Error;
+use anyhow::{anyhow, Error};
+use serde_json::Value;
 
-use crate::{DeleteParams, Doc, PaginationResult, PatchParams, UpdateResult, UpsertResult};
+use crate::{
+    AggregateParams, Condition, DeleteParams, Doc, Facet, InsertResult, Namespace, OnConflict,
+    PaginationResult, PatchParams, ReplaceResult, Returning, SearchConfig, UpdateResult,
+    UpsertResult, WriteGuard,
+};
 
-use super::{pagination::PaginationParams, response::DeleteResult, RestModel};
+use super::{pagination::PaginationParams, response::DeleteResult, retry::Transient, RestModel};
 
 pub trait DbClient<T>: Sync
 where
@@ -10,12 +15,56 @@ where
 {
     fn generate_id(&self) -> String;
 
+    /// Maps a model's compile-time `db_name`/`table_name` to the names used for
+    /// this client, rejecting anything that is not a safe identifier.
+    fn resolve(&self, db_name: &str, table_name: &str) -> Result<(String, String), Error> {
+        Namespace::default().resolve(db_name, table_name)
+    }
+
+    /// Whether `error`, returned by this client, is worth retrying; see `RetryDb`.
+    fn transient(&self, _error: &Error) -> Option<Transient> {
+        None
+    }
+
+    /// Whether `error`, returned by a write, was caused by the documents
+    /// written, e.g. a value the backend rejects, rather than by the
+    /// connection or the statement; `import` retries smaller batches only then.
+    fn data_error(&self, _error: &Error) -> bool {
+        false
+    }
+
     fn init(
         &self,
         db_name: &str,
         table_name: &str,
     ) -> impl std::future::Future<Output = Result<(), Error>>;
 
+    /// Makes the backend reject rows whose `data` doesn't match the JSON `schema`.
+    fn install_schema(
+        &self,
+        _db_name: &str,
+        _table_name: &str,
+        _schema: &Value,
+    ) -> impl std::future::Future<Output = Result<(), Error>> {
+        async {
+            Err(anyhow!(
+                "schema enforcement is not supported by this client"
+            ))
+        }
+    }
+
+    /// Indexes the `search` fields so `PaginationParams::q` can search them;
+    /// rebuilds the index when `search` changed since the last call, which
+    /// may rewrite the whole table under an exclusive lock.
+    fn install_search(
+        &self,
+        _db_name: &str,
+        _table_name: &str,
+        _search: &SearchConfig,
+    ) -> impl std::future::Future<Output = Result<(), Error>> {
+        async { Err(anyhow!("full-text search is not supported by this client")) }
+    }
+
     /// GET /resources/:id
     fn select_by_id(
         &self,
@@ -24,6 +73,47 @@ where
         id: &str,
     ) -> impl std::future::Future<Output = Result<Doc<T>, Error>>;
 
+    /// The documents among `ids` that exist, in no particular order.
+    ///
+    /// Defaults to one `paginate` with an `In` filter on `_id`.
+    fn select_by_ids(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        ids: &[&str],
+    ) -> impl std::future::Future<Output = Result<Vec<Doc<T>>, Error>> {
+        async move {
+            if ids.is_empty() {
+                return Ok(vec![]);
+            }
+            let params = PaginationParams {
+                limit: Some(ids.len() as u32),
+                filter: Some(Condition::In(
+                    "_id".to_string(),
+                    Value::Array(ids.iter().map(|id| Value::String(id.to_string())).collect()),
+                )),
+                ..Default::default()
+            };
+            Ok(self.paginate(db_name, table_name, &params).await?.items)
+        }
+    }
+
+    /// Fills `_expanded` of `docs` with the documents their reference `fields`
+    /// point at; see `RestModel::get_references`.
+    fn expand(
+        &self,
+        _docs: &mut [Doc<T>],
+        fields: &[&str],
+    ) -> impl std::future::Future<Output = Result<(), Error>> {
+        let supported = fields.is_empty();
+        async move {
+            if supported {
+                return Ok(());
+            }
+            Err(anyhow!("expand is not supported by this client"))
+        }
+    }
+
     /// GET /resources
     fn paginate(
         &self,
@@ -32,13 +122,87 @@ where
         pagination_params: &PaginationParams,
     ) -> impl std::future::Future<Output = Result<PaginationResult<T>, Error>>;
 
+    /// One JSON object per group; see `AggregateParams`.
+    fn aggregate(
+        &self,
+        _db_name: &str,
+        _table_name: &str,
+        _params: &AggregateParams,
+    ) -> impl std::future::Future<Output = Result<Vec<Value>, Error>> {
+        async { Err(anyhow!("aggregate is not supported by this client")) }
+    }
+
+    /// The values of `field` among documents matching `filter`, most common
+    /// first; documents without the field, or with it `null`, are left out.
+    fn distinct(
+        &self,
+        _db_name: &str,
+        _table_name: &str,
+        _field: &str,
+        _filter: Option<&Condition>,
+        _limit: Option<u32>,
+    ) -> impl std::future::Future<Output = Result<Vec<Facet>, Error>> {
+        async { Err(anyhow!("distinct is not supported by this client")) }
+    }
+
     /// PUT /resources
     fn upsert(
         &self,
         db_name: &str,
         table_name: &str,
         items: &[Doc<T>],
-    ) -> impl std::future::Future<Output = Result<UpsertResult, Error>>;
+        returning: Returning,
+    ) -> impl std::future::Future<Output = Result<UpsertResult<T>, Error>>;
+
+    /// POST /resources
+    ///
+    /// Never overwrites: existing ids are rejected or skipped per `on_conflict`.
+    fn insert(
+        &self,
+        _db_name: &str,
+        _table_name: &str,
+        _items: &[Doc<T>],
+        _on_conflict: OnConflict,
+    ) -> impl std::future::Future<Output = Result<InsertResult, Error>> {
+        async { Err(anyhow!("insert is not supported by this client")) }
+    }
+
+    /// Overwrites existing documents only; fails with `Missing` listing the
+    /// absent ids and writes nothing if any of `items` doesn't exist.
+    fn replace(
+        &self,
+        _db_name: &str,
+        _table_name: &str,
+        _items: &[Doc<T>],
+    ) -> impl std::future::Future<Output = Result<ReplaceResult, Error>> {
+        async { Err(anyhow!("replace is not supported by this client")) }
+    }
+
+    /// Like `upsert` with `Returning::None`, within `guard`: writes nothing
+    /// and fails with `Denied` if an existing row is outside `guard.filter` or
+    /// a new document sets a protected field, or with `Missing` if
+    /// `guard.replace_only` and any of `items` doesn't exist. The guard must be
+    /// checked by the write itself, against the primary.
+    fn upsert_guarded(
+        &self,
+        _db_name: &str,
+        _table_name: &str,
+        _items: &[Doc<T>],
+        _guard: &WriteGuard,
+    ) -> impl std::future::Future<Output = Result<UpsertResult<T>, Error>> {
+        async { Err(anyhow!("guarded upsert is not supported by this client")) }
+    }
+
+    /// Bulk-loads new documents as fast as the backend allows and returns how
+    /// many were written; an existing `_id` fails the whole call.
+    fn copy_in(
+        &self,
+        _db_name: &str,
+        _table_name: &str,
+        _items: &[Doc<T>],
+    ) -> impl std::future::Future<Output = Result<u32, Error>> {
+        async { Err(anyhow!("copy is not supported by this client")) }
+    }
 
     /// PATCH /resources
     fn update(
@@ -46,7 +210,8 @@ where
         db_name: &str,
         table_name: &str,
         params: &PatchParams,
-    ) -> impl std::future::Future<Output = Result<UpdateResult, Error>>;
+        returning: Returning,
+    ) -> impl std::future::Future<Output = Result<UpdateResult<T>, Error>>;
 
     /// DELETE /resources
     fn delete(
@@ -54,5 +219,6 @@ where
         db_name: &str,
         table_name: &str,
         filter: &DeleteParams,
-    ) -> impl std::future::Future<Output = Result<DeleteResult, Error>>;
+        returning: Returning,
+    ) -> impl std::future::Future<Output = Result<DeleteResult<T>, Error>>;
 }
@@ -1,5 +1,6 @@
 use chrono::Utc;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use crate::{DbClient, RestModel};
 
@@ -9,6 +10,14 @@ pub struct Doc<T> {
     pub data: T,
     pub _created_at: i64,
     pub _updated_at: i64,
+    /// Referenced documents by reference field, filled when asked to `expand`;
+    /// `null` for a dangling reference.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub _expanded: Map<String, Value>,
+    /// Search fields matching `PaginationParams::q`, by field, HTML-escaped
+    /// with the matches wrapped in `<mark>`; filled only by a search.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub _highlights: Map<String, Value>,
 }
 
 impl<T> Doc<T>
@@ -21,6 +30,21 @@ where
             data,
             _created_at: Utc::now().timestamp_millis(),
             _updated_at: Utc::now().timestamp_millis(),
+            _expanded: Map::new(),
+            _highlights: Map::new(),
+        }
+    }
+}
+
+impl<T> Doc<T> {
+    /// The document `field` references, if it was expanded and exists.
+    pub fn expanded<R: DeserializeOwned>(
+        &self,
+        field: &str,
+    ) -> Result<Option<Doc<R>>, serde_json::Error> {
+        match self._expanded.get(field) {
+            None | Some(Value::Null) => Ok(None),
+            Some(doc) => serde_json::from_value(doc.clone()).map(Some),
         }
     }
 }
@@ -0,0 +1,118 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// The requested document does not exist.
+///
+/// Returned inside `anyhow::Error`; match with `err.downcast_ref::<NotFound>()`.
+#[derive(Debug, Clone)]
+pub struct NotFound;
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Document not found")
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+/// A `Policy` refused the principal access.
+///
+/// Returned inside `anyhow::Error`; match with `err.downcast_ref::<Denied>()`.
+#[derive(Debug, Clone)]
+pub struct Denied {
+    pub reason: String,
+}
+
+impl Denied {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Denied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Permission denied: {}", self.reason)
+    }
+}
+
+impl std::error::Error for Denied {}
+
+/// An insert-only write hit documents that already exist.
+///
+/// Returned inside `anyhow::Error`; match with `err.downcast_ref::<Conflict>()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conflict {
+    pub ids: Vec<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Documents already exist: {}", self.ids.join(", "))
+    }
+}
+
+impl std::error::Error for Conflict {}
+
+/// A replace-only write named documents that don't exist.
+///
+/// Returned inside `anyhow::Error`; match with `err.downcast_ref::<Missing>()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Missing {
+    pub ids: Vec<String>,
+}
+
+impl fmt::Display for Missing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Documents not found: {}", self.ids.join(", "))
+    }
+}
+
+impl std::error::Error for Missing {}
+
+/// A delete matched documents that others still reference through a
+/// `restrict` reference; nothing was deleted.
+///
+/// Returned inside `anyhow::Error`; match with `err.downcast_ref::<Referenced>()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Referenced {
+    pub table: String,
+    pub field: String,
+    pub count: u32,
+}
+
+impl fmt::Display for Referenced {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} document(s) in {} still reference them via {}",
+            self.count, self.table, self.field
+        )
+    }
+}
+
+impl std::error::Error for Referenced {}
+
+/// A `RetryDb` circuit breaker is open after repeated transient failures; the
+/// call was not attempted.
+///
+/// Returned inside `anyhow::Error`; match with `err.downcast_ref::<CircuitOpen>()`.
+#[derive(Debug, Clone)]
+pub struct CircuitOpen {
+    /// Until the breaker lets a trial call through.
+    pub retry_after: std::time::Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Circuit breaker is open, retry after {}ms",
+            self.retry_after.as_millis()
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
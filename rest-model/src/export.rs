@@ -0,0 +1,116 @@
+use anyhow::Error;
+use futures_util::{stream, Stream, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::Doc;
+
+/// One JSON-encoded `Doc` per line.
+///
+/// Each item is a chunk ready for a file (`write_all`) or a streamed HTTP body.
+pub fn ndjson<T, S>(docs: S) -> impl Stream<Item = Result<String, Error>>
+where
+    T: Serialize,
+    S: Stream<Item = Result<Doc<T>, Error>>,
+{
+    docs.map(|doc| {
+        let mut line = serde_json::to_string(&doc?)?;
+        line.push('\n');
+        Ok(line)
+    })
+}
+
+/// CSV columns, each a header and a dotted field path:
+///
+/// ```
+/// use rest_model::export::Csv;
+///
+/// let csv = Csv::default()
+///     .column("id", "_id")
+///     .column("name", "name")
+///     .column("country", "address.country");
+/// ```
+///
+/// Paths follow `Condition` fields: `_id`, `_created_at` and `_updated_at`
+/// are the system fields, anything else is looked up in `data`. Missing
+/// values and `null` become empty cells, arrays and objects are written as JSON.
+#[derive(Debug, Clone, Default)]
+pub struct Csv {
+    /// `(header, field path)` pairs.
+    pub columns: Vec<(String, String)>,
+}
+
+impl Csv {
+    pub fn column(mut self, header: &str, path: &str) -> Self {
+        self.columns.push((header.to_string(), path.to_string()));
+        self
+    }
+
+    /// The header line followed by one line per document.
+    pub fn encode<T, S>(self, docs: S) -> impl Stream<Item = Result<String, Error>>
+    where
+        T: Serialize,
+        S: Stream<Item = Result<Doc<T>, Error>>,
+    {
+        let header = line(self.columns.iter().map(|(header, _)| header.clone()));
+        stream::once(async { Ok(header) }).chain(docs.map(move |doc| {
+            let doc = doc?;
+            let data = serde_json::to_value(&doc.data)?;
+            Ok(line(self.columns.iter().map(|(_, path)| {
+                match path.as_str() {
+                    "_id" => doc._id.clone(),
+                    "_created_at" => doc._created_at.to_string(),
+                    "_updated_at" => doc._updated_at.to_string(),
+                    path => path
+                        .split('.')
+                        .try_fold(&data, |value, key| value.get(key))
+                        .map(cell)
+                        .unwrap_or_default(),
+                }
+            })))
+        }))
+    }
+}
+
+/// Writes `chunks` from `ndjson` or `Csv::encode` to `out`, e.g. a
+/// `tokio::fs::File`, and returns the number of bytes written.
+pub async fn write_all<S, W>(chunks: S, out: &mut W) -> Result<u64, Error>
+where
+    S: Stream<Item = Result<String, Error>>,
+    W: AsyncWrite + Unpin,
+{
+    let mut chunks = std::pin::pin!(chunks);
+    let mut written = 0;
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk?;
+        out.write_all(chunk.as_bytes()).await?;
+        written += chunk.len() as u64;
+    }
+    out.flush().await?;
+    Ok(written)
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Joins `cells` into a CSV line, quoting them as RFC 4180 requires.
+fn line(cells: impl Iterator<Item = String>) -> String {
+    let mut line = cells
+        .map(|cell| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+    line
+}
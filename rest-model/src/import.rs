@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Error};
+use chrono::Utc;
+use futures_util::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, Lines};
+
+use crate::{export::Csv, DbClient, Doc, RestModel, Returning};
+
+/// Documents written per `upsert` or `copy_in` call unless set otherwise;
+/// an `upsert` binds two parameters per document, well under Postgres's 65535.
+const BATCH_SIZE: u32 = 1000;
+
+/// One input record: the line it starts on and the document, or why it
+/// couldn't be read.
+#[derive(Debug)]
+pub struct Record<T> {
+    pub line: usize,
+    pub doc: Result<Doc<T>, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Create or overwrite by `_id`, like `PUT /resources`.
+    #[default]
+    Upsert,
+    /// The backend's bulk load (`COPY` in Postgres): fastest, but insert-only,
+    /// so a record whose `_id` already exists fails.
+    Copy,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportParams {
+    /// Defaults to 1000.
+    pub batch_size: Option<u32>,
+    #[serde(default)]
+    pub mode: ImportMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedLine {
+    pub line: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub imported_count: u32,
+    pub failed: Vec<FailedLine>,
+}
+
+/// Reads one JSON object per line, either a `Doc` as written by
+/// `export::ndjson` or just the model's `data`, which gets a new `_id`.
+/// Blank lines are skipped.
+pub fn ndjson<'a, T, Db, R>(
+    client: &'a Db,
+    reader: R,
+) -> impl Stream<Item = Result<Record<T>, Error>> + 'a
+where
+    T: RestModel + 'a,
+    Db: DbClient<T>,
+    R: AsyncBufRead + Unpin + 'a,
+{
+    let lines = stream::unfold((reader.lines(), 0), |(mut lines, line)| async move {
+        match lines.next_line().await {
+            Ok(Some(text)) => Some((Ok((line + 1, text)), (lines, line + 1))),
+            Ok(None) => None,
+            Err(e) => Some((Err(Error::from(e)), (lines, line))),
+        }
+    });
+    lines.filter_map(move |text| async move {
+        let (line, text) = match text {
+            Ok((_, text)) if text.trim().is_empty() => return None,
+            Ok(text) => text,
+            Err(e) => return Some(Err(e)),
+        };
+        let doc = serde_json::from_str::<Value>(&text)
+            .and_then(
+                |value| match serde_json::from_value::<Doc<T>>(value.clone()) {
+                    Ok(doc) => Ok(doc),
+                    Err(_) => serde_json::from_value(value).map(|data| Doc::new(client, data)),
+                },
+            )
+            .map_err(|e| e.to_string());
+        Some(Ok(Record { line, doc }))
+    })
+}
+
+impl Csv {
+    /// Reads CSV with a header line, mapping each header to its column's
+    /// field path; without columns the headers are the paths themselves.
+    ///
+    /// Empty cells are left out, cells holding valid JSON (numbers, booleans,
+    /// arrays, objects) are read as JSON and anything else as a string, so a
+    /// string field of digits must be written as JSON, e.g. `"""007"""`.
+    /// Rows without an `_id` column get a new one.
+    pub fn decode<'a, T, Db, R>(
+        self,
+        client: &'a Db,
+        reader: R,
+    ) -> impl Stream<Item = Result<Record<T>, Error>> + 'a
+    where
+        T: RestModel + 'a,
+        Db: DbClient<T>,
+        R: AsyncBufRead + Unpin + 'a,
+    {
+        let reader = CsvReader {
+            lines: reader.lines(),
+            line: 0,
+            columns: self.columns,
+            paths: None,
+        };
+        stream::unfold(reader, move |mut reader| async move {
+            let record = reader.next(client).await?;
+            Some((record, reader))
+        })
+    }
+}
+
+struct CsvReader<R> {
+    lines: Lines<R>,
+    /// The last line read.
+    line: usize,
+    columns: Vec<(String, String)>,
+    /// Field path per cell, once the header is read; empty for ignored cells.
+    paths: Option<Vec<String>>,
+}
+
+impl<R: AsyncBufRead + Unpin> CsvReader<R> {
+    async fn next<T, Db>(&mut self, client: &Db) -> Option<Result<Record<T>, Error>>
+    where
+        T: RestModel,
+        Db: DbClient<T>,
+    {
+        loop {
+            let (line, cells) = match self.read_record().await {
+                Ok(Some(record)) => record,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+            let Some(paths) = &self.paths else {
+                let headers = match cells {
+                    Ok(headers) => headers,
+                    Err(reason) => {
+                        return Some(Err(anyhow!("invalid header on line {}: {}", line, reason)))
+                    }
+                };
+                self.paths = Some(headers.into_iter().map(|h| self.path(h)).collect());
+                continue;
+            };
+            let doc = match cells {
+                Ok(cells) if cells.iter().all(|cell| cell.is_empty()) => continue,
+                Ok(cells) => row_to_doc(client, paths, cells),
+                Err(reason) => Err(reason),
+            };
+            return Some(Ok(Record { line, doc }));
+        }
+    }
+
+    fn path(&self, header: String) -> String {
+        if self.columns.is_empty() {
+            return header;
+        }
+        self.columns
+            .iter()
+            .find(|(h, _)| *h == header)
+            .map(|(_, path)| path.clone())
+            .unwrap_or_default()
+    }
+
+    /// The next record as `(first line, cells)`, joining lines while a
+    /// quoted cell is still open.
+    async fn read_record(&mut self) -> Result<Option<(usize, Result<Vec<String>, String>)>, Error> {
+        let Some(mut text) = self.lines.next_line().await? else {
+            return Ok(None);
+        };
+        self.line += 1;
+        let start = self.line;
+        while text.matches('"').count() % 2 == 1 {
+            let Some(next) = self.lines.next_line().await? else {
+                break;
+            };
+            self.line += 1;
+            text.push('\n');
+            text.push_str(&next);
+        }
+        Ok(Some((start, split_record(&text))))
+    }
+}
+
+/// Writes `records` in batches of `params.batch_size`, collecting every
+/// unreadable record and every record that fails to write into the report.
+/// A batch failing on its data (see `DbClient::data_error`) is split in
+/// halves and retried until each failure is pinned on its own line. In
+/// `Upsert` mode the last record of an `_id` within a batch wins, as writing
+/// them one by one would.
+///
+/// Errors reading the input and any other write error, e.g. a lost
+/// connection, abort the import; batches written before stay written.
+pub async fn import<T, Db, S>(
+    client: &Db,
+    records: S,
+    params: &ImportParams,
+) -> Result<ImportReport, Error>
+where
+    T: RestModel,
+    Db: DbClient<T>,
+    S: Stream<Item = Result<Record<T>, Error>>,
+{
+    let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+    let batch_size = params.batch_size.unwrap_or(BATCH_SIZE).max(1) as usize;
+    let mut report = ImportReport::default();
+    let mut records = std::pin::pin!(records.chunks(batch_size));
+    while let Some(chunk) = records.next().await {
+        let mut lines = vec![];
+        let mut docs = vec![];
+        for record in chunk {
+            let record = record?;
+            match record.doc {
+                Ok(doc) => {
+                    lines.push(record.line);
+                    docs.push(doc);
+                }
+                Err(reason) => report.failed.push(FailedLine {
+                    line: record.line,
+                    reason,
+                }),
+            }
+        }
+        if docs.is_empty() {
+            continue;
+        }
+        if params.mode == ImportMode::Upsert {
+            (lines, docs) = last_per_id(lines, docs);
+        }
+        let mut pending = vec![(lines, docs)];
+        while let Some((mut lines, mut docs)) = pending.pop() {
+            match write(client, params.mode, &db_name, &table_name, &docs).await {
+                Ok(count) => report.imported_count += count,
+                // an outage or a bad statement would fail every half too
+                Err(e) if client.transient(&e).is_some() || !client.data_error(&e) => {
+                    return Err(e)
+                }
+                Err(e) if docs.len() == 1 => report.failed.push(FailedLine {
+                    line: lines[0],
+                    reason: e.to_string(),
+                }),
+                Err(_) => {
+                    let mid = docs.len() / 2;
+                    pending.push((lines.split_off(mid), docs.split_off(mid)));
+                    pending.push((lines, docs));
+                }
+            }
+        }
+    }
+    report.failed.sort_by_key(|failed| failed.line);
+    Ok(report)
+}
+
+async fn write<T, Db>(
+    client: &Db,
+    mode: ImportMode,
+    db_name: &str,
+    table_name: &str,
+    docs: &[Doc<T>],
+) -> Result<u32, Error>
+where
+    T: RestModel,
+    Db: DbClient<T>,
+{
+    match mode {
+        ImportMode::Upsert => client
+            .upsert(db_name, table_name, docs, Returning::None)
+            .await
+            .map(|result| result.created_count + result.updated_count),
+        ImportMode::Copy => client.copy_in(db_name, table_name, docs).await,
+    }
+}
+
+/// Drops every document followed by another with the same `_id`; one
+/// statement can't write a row twice.
+fn last_per_id<T>(lines: Vec<usize>, docs: Vec<Doc<T>>) -> (Vec<usize>, Vec<Doc<T>>) {
+    let last: HashMap<String, usize> = docs
+        .iter()
+        .enumerate()
+        .map(|(i, doc)| (doc._id.clone(), i))
+        .collect();
+    lines
+        .into_iter()
+        .zip(docs)
+        .enumerate()
+        .filter(|(i, (_, doc))| last[&doc._id] == *i)
+        .map(|(_, record)| record)
+        .unzip()
+}
+
+/// Splits one CSV record into cells, undoing RFC 4180 quoting.
+fn split_record(text: &str) -> Result<Vec<String>, String> {
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut chars = text.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            '"' if quoted => {
+                quoted = false;
+                if !matches!(chars.peek(), None | Some(',')) {
+                    return Err("unexpected character after closing quote".to_string());
+                }
+            }
+            '"' if cell.is_empty() => quoted = true,
+            ',' if !quoted => cells.push(std::mem::take(&mut cell)),
+            c => cell.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quoted cell".to_string());
+    }
+    cells.push(cell);
+    Ok(cells)
+}
+
+fn row_to_doc<T, Db>(client: &Db, paths: &[String], cells: Vec<String>) -> Result<Doc<T>, String>
+where
+    T: RestModel,
+    Db: DbClient<T>,
+{
+    if cells.len() != paths.len() {
+        return Err(format!(
+            "expected {} cells, found {}",
+            paths.len(),
+            cells.len()
+        ));
+    }
+    let now = Utc::now().timestamp_millis();
+    let mut id = None;
+    let (mut created_at, mut updated_at) = (now, now);
+    let mut data = Value::Object(Map::new());
+    for (path, cell) in paths.iter().zip(cells) {
+        if path.is_empty() || cell.is_empty() {
+            continue;
+        }
+        match path.as_str() {
+            "_id" => id = Some(cell),
+            "_created_at" => created_at = timestamp(path, &cell)?,
+            "_updated_at" => updated_at = timestamp(path, &cell)?,
+            path => {
+                let value = serde_json::from_str(&cell).unwrap_or(Value::String(cell));
+                let mut target = &mut data;
+                for key in path.split('.') {
+                    let Value::Object(object) = target else {
+                        return Err(format!("{} is nested in a non-object", path));
+                    };
+                    target = object.entry(key).or_insert(Value::Object(Map::new()));
+                }
+                *target = value;
+            }
+        }
+    }
+    let data: T = serde_json::from_value(data).map_err(|e| e.to_string())?;
+    Ok(Doc {
+        _id: id.unwrap_or_else(|| client.generate_id()),
+        data,
+        _created_at: created_at,
+        _updated_at: updated_at,
+        _expanded: Map::new(),
+        _highlights: Map::new(),
+    })
+}
+
+fn timestamp(path: &str, cell: &str) -> Result<i64, String> {
+    cell.parse()
+        .map_err(|_| format!("{} is not a timestamp: {}", path, cell))
+}
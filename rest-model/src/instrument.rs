@@ -0,0 +1,293 @@
+use std::{future::Future, time::Instant};
+
+use anyhow::Error;
+use serde_json::Value;
+use tracing::{field, info_span, Instrument};
+
+use crate::{
+    pagination::PaginationParams, retry::Transient, AggregateParams, Condition, DbClient,
+    DeleteParams, DeleteResult, Doc, Facet, InsertResult, OnConflict, PaginationResult,
+    PatchParams, ReplaceResult, RestModel, Returning, SearchConfig, UpdateResult, UpsertResult,
+    WriteGuard,
+};
+
+/// Histogram of call durations in seconds.
+pub const DURATION_SECONDS: &str = "rest_model_db_duration_seconds";
+/// Counter of failed calls.
+pub const ERRORS_TOTAL: &str = "rest_model_db_errors_total";
+/// Counter of documents returned or written.
+pub const ROWS_TOTAL: &str = "rest_model_db_rows_total";
+
+/// Wraps any `DbClient` to report every call through the `metrics` facade and
+/// a `rest_model.db` `tracing` span.
+///
+/// All metrics carry `db`, `table`, `model` (the Rust type name) and
+/// `operation` (the `DbClient` method) labels; install any `metrics` recorder,
+/// e.g. a Prometheus exporter, to collect them. Spans carry the same fields
+/// plus `rows` and, on failure, `error`.
+pub struct InstrumentedDb<D> {
+    inner: D,
+}
+
+impl<D> InstrumentedDb<D> {
+    pub fn new(inner: D) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    async fn observe<T, R>(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        operation: &'static str,
+        rows: impl FnOnce(&R) -> usize,
+        call: impl Future<Output = Result<R, Error>>,
+    ) -> Result<R, Error> {
+        let model = model_name::<T>();
+        let span = info_span!(
+            "rest_model.db",
+            db = db_name,
+            table = table_name,
+            model,
+            operation,
+            rows = field::Empty,
+            error = field::Empty,
+        );
+        let labels = [
+            ("db", db_name.to_string()),
+            ("table", table_name.to_string()),
+            ("model", model.to_string()),
+            ("operation", operation.to_string()),
+        ];
+        let start = Instant::now();
+        let result = call.instrument(span.clone()).await;
+        metrics::histogram!(DURATION_SECONDS, &labels).record(start.elapsed().as_secs_f64());
+        match &result {
+            Ok(value) => {
+                let rows = rows(value);
+                span.record("rows", rows);
+                metrics::counter!(ROWS_TOTAL, &labels).increment(rows as u64);
+            }
+            Err(e) => {
+                span.record("error", field::display(e));
+                metrics::counter!(ERRORS_TOTAL, &labels).increment(1);
+            }
+        }
+        result
+    }
+}
+
+/// `User` for `my_crate::models::User`.
+fn model_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let path = name.split('<').next().unwrap_or(name);
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+impl<T, D> DbClient<T> for InstrumentedDb<D>
+where
+    T: RestModel,
+    D: DbClient<T>,
+{
+    fn generate_id(&self) -> String {
+        self.inner.generate_id()
+    }
+
+    fn resolve(&self, db_name: &str, table_name: &str) -> Result<(String, String), Error> {
+        self.inner.resolve(db_name, table_name)
+    }
+
+    fn transient(&self, error: &Error) -> Option<Transient> {
+        self.inner.transient(error)
+    }
+
+    fn data_error(&self, error: &Error) -> bool {
+        self.inner.data_error(error)
+    }
+
+    async fn init(&self, db_name: &str, table_name: &str) -> Result<(), Error> {
+        let call = self.inner.init(db_name, table_name);
+        self.observe::<T, _>(db_name, table_name, "init", |_| 0, call)
+            .await
+    }
+
+    async fn install_schema(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        schema: &Value,
+    ) -> Result<(), Error> {
+        let call = self.inner.install_schema(db_name, table_name, schema);
+        self.observe::<T, _>(db_name, table_name, "install_schema", |_| 0, call)
+            .await
+    }
+
+    async fn install_search(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        search: &SearchConfig,
+    ) -> Result<(), Error> {
+        let call = self.inner.install_search(db_name, table_name, search);
+        self.observe::<T, _>(db_name, table_name, "install_search", |_| 0, call)
+            .await
+    }
+
+    async fn select_by_id(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        id: &str,
+    ) -> Result<Doc<T>, Error> {
+        let call = self.inner.select_by_id(db_name, table_name, id);
+        self.observe::<T, _>(db_name, table_name, "select_by_id", |_| 1, call)
+            .await
+    }
+
+    async fn select_by_ids(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        ids: &[&str],
+    ) -> Result<Vec<Doc<T>>, Error> {
+        let call = self.inner.select_by_ids(db_name, table_name, ids);
+        self.observe::<T, _>(db_name, table_name, "select_by_ids", Vec::len, call)
+            .await
+    }
+
+    async fn expand(&self, docs: &mut [Doc<T>], fields: &[&str]) -> Result<(), Error> {
+        let (db_name, table_name) = self.resolve(T::get_db_name(), T::get_table_name())?;
+        let count = docs.len();
+        let call = self.inner.expand(docs, fields);
+        self.observe::<T, _>(&db_name, &table_name, "expand", |_| count, call)
+            .await
+    }
+
+    async fn paginate(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        pagination_params: &PaginationParams,
+    ) -> Result<PaginationResult<T>, Error> {
+        let call = self.inner.paginate(db_name, table_name, pagination_params);
+        self.observe::<T, _>(db_name, table_name, "paginate", |r| r.items.len(), call)
+            .await
+    }
+
+    async fn aggregate(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        params: &AggregateParams,
+    ) -> Result<Vec<Value>, Error> {
+        let call = self.inner.aggregate(db_name, table_name, params);
+        self.observe::<T, _>(db_name, table_name, "aggregate", Vec::len, call)
+            .await
+    }
+
+    async fn distinct(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        field: &str,
+        filter: Option<&Condition>,
+        limit: Option<u32>,
+    ) -> Result<Vec<Facet>, Error> {
+        let call = self
+            .inner
+            .distinct(db_name, table_name, field, filter, limit);
+        self.observe::<T, _>(db_name, table_name, "distinct", Vec::len, call)
+            .await
+    }
+
+    async fn upsert(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        returning: Returning,
+    ) -> Result<UpsertResult<T>, Error> {
+        let call = self.inner.upsert(db_name, table_name, items, returning);
+        let rows = |r: &UpsertResult<T>| (r.created_count + r.updated_count) as usize;
+        self.observe::<T, _>(db_name, table_name, "upsert", rows, call)
+            .await
+    }
+
+    async fn insert(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        on_conflict: OnConflict,
+    ) -> Result<InsertResult, Error> {
+        let call = self.inner.insert(db_name, table_name, items, on_conflict);
+        let rows = |r: &InsertResult| r.created_count as usize;
+        self.observe::<T, _>(db_name, table_name, "insert", rows, call)
+            .await
+    }
+
+    async fn replace(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+    ) -> Result<ReplaceResult, Error> {
+        let call = self.inner.replace(db_name, table_name, items);
+        let rows = |r: &ReplaceResult| r.replaced_count as usize;
+        self.observe::<T, _>(db_name, table_name, "replace", rows, call)
+            .await
+    }
+
+    async fn upsert_guarded(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        guard: &WriteGuard,
+    ) -> Result<UpsertResult<T>, Error> {
+        let call = self.inner.upsert_guarded(db_name, table_name, items, guard);
+        let rows = |r: &UpsertResult<T>| (r.created_count + r.updated_count) as usize;
+        self.observe::<T, _>(db_name, table_name, "upsert_guarded", rows, call)
+            .await
+    }
+
+    async fn copy_in(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+    ) -> Result<u32, Error> {
+        let call = self.inner.copy_in(db_name, table_name, items);
+        self.observe::<T, _>(db_name, table_name, "copy_in", |n| *n as usize, call)
+            .await
+    }
+
+    async fn update(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        params: &PatchParams,
+        returning: Returning,
+    ) -> Result<UpdateResult<T>, Error> {
+        let call = self.inner.update(db_name, table_name, params, returning);
+        let rows = |r: &UpdateResult<T>| r.updated_count as usize;
+        self.observe::<T, _>(db_name, table_name, "update", rows, call)
+            .await
+    }
+
+    async fn delete(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        filter: &DeleteParams,
+        returning: Returning,
+    ) -> Result<DeleteResult<T>, Error> {
+        let call = self.inner.delete(db_name, table_name, filter, returning);
+        let rows = |r: &DeleteResult<T>| r.deleted_count as usize;
+        self.observe::<T, _>(db_name, table_name, "delete", rows, call)
+            .await
+    }
+}
@@ -1,14 +1,35 @@
+mod aggregate;
+pub mod cache;
 mod db_client;
 mod doc;
+mod error;
+pub mod export;
+pub mod import;
+pub mod instrument;
 pub mod method;
+mod namespace;
+pub mod oid;
+pub mod openapi;
 pub mod pagination;
 mod params;
+mod policy;
 mod response;
 mod rest_model;
+pub mod retry;
+mod schema;
+pub mod typescript;
 
+pub use aggregate::*;
 pub use db_client::*;
 pub use doc::*;
+pub use error::*;
+pub use namespace::*;
 pub use params::*;
+pub use policy::*;
 pub use response::*;
 pub use rest_model::*;
 pub use rest_model_macro::*;
+pub use schema::*;
+
+#[doc(hidden)]
+pub use serde_json;
@@ -1,8 +1,14 @@
+use std::collections::{HashMap, HashSet};
+
 use anyhow::Error;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 
 use crate::{
-    db_client::DbClient, pagination::PaginationParams, response::DeleteResult, DeleteParams, Doc,
-    PaginationResult, PatchParams, RestModel, UpdateResult, UpsertResult,
+    db_client::DbClient, pagination::PaginationParams, response::DeleteResult, restrict,
+    AggregateParams, Condition, DeleteParams, Denied, Doc, Facet, InsertResult, ModelSchema,
+    OnConflict, PaginationResult, PatchParams, Policy, ReplaceResult, RestModel, Returning,
+    UpdateResult, UpsertResult, WriteGuard,
 };
 
 pub trait Init<T, Db>
@@ -11,7 +17,42 @@ where
     Db: DbClient<T>,
 {
     fn init(client: &Db) -> impl std::future::Future<Output = Result<(), Error>> {
-        async { client.init(T::get_db_name(), T::get_table_name()).await }
+        async {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            client.init(&db_name, &table_name).await
+        }
+    }
+
+    /// Builds the search index of `T`'s `search(..)`, which
+    /// `PaginationParams::q` needs, or rebuilds it after the configuration
+    /// changed. A rebuild can rewrite the whole table under an exclusive
+    /// lock, so this is a migration to run deliberately, not at every startup.
+    fn install_search(client: &Db) -> impl std::future::Future<Output = Result<(), Error>> {
+        async {
+            let Some(search) = T::get_search() else {
+                return Err(anyhow::anyhow!(
+                    "{} declares no search",
+                    T::get_table_name()
+                ));
+            };
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            client.install_search(&db_name, &table_name, &search).await
+        }
+    }
+
+    /// Like `init`, then installs `T`'s data schema so rows written by other
+    /// services or manual SQL can't corrupt the table.
+    fn init_with_schema(client: &Db) -> impl std::future::Future<Output = Result<(), Error>>
+    where
+        T: ModelSchema,
+    {
+        async {
+            Self::init(client).await?;
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            client
+                .install_schema(&db_name, &table_name, &T::get_data_schema())
+                .await
+        }
     }
 }
 
@@ -25,9 +66,89 @@ where
         id: &str,
     ) -> impl std::future::Future<Output = Result<Doc<T>, Error>> {
         async {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            client.select_by_id(&db_name, &table_name, id).await
+        }
+    }
+
+    /// Like `get_with_id`, embedding the documents referenced by `expand` in `_expanded`.
+    fn get_with_id_expanded(
+        client: &Db,
+        id: &str,
+        expand: &[&str],
+    ) -> impl std::future::Future<Output = Result<Doc<T>, Error>> {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            let mut doc = client.select_by_id(&db_name, &table_name, id).await?;
             client
-                .select_by_id(T::get_db_name(), T::get_table_name(), id)
-                .await
+                .expand(std::slice::from_mut(&mut doc), expand)
+                .await?;
+            Ok(doc)
+        }
+    }
+
+    /// Like `get_with_id`, but fails with `Denied` when the document exists
+    /// outside `T::read_filter(principal)`, and leaves out the fields
+    /// `principal` cannot read.
+    fn get_with_id_as<P>(
+        client: &Db,
+        principal: &P,
+        id: &str,
+    ) -> impl std::future::Future<Output = Result<Doc<Value>, Error>>
+    where
+        T: Policy<P>,
+    {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            let Some(extra) = T::read_filter(principal) else {
+                let doc = client.select_by_id(&db_name, &table_name, id).await?;
+                return Ok(T::mask(principal, &doc)?);
+            };
+            // one read returns the document only if it passes the filter, so a
+            // replica or cache can't pair a row with a check of another version
+            let params = PaginationParams {
+                limit: Some(1),
+                filter: Some(restrict(
+                    Condition::Eq("_id".to_string(), Value::String(id.to_string())),
+                    Some(extra),
+                )),
+                ..Default::default()
+            };
+            let visible = client.paginate(&db_name, &table_name, &params).await?;
+            match visible.items.first() {
+                Some(doc) => Ok(T::mask(principal, doc)?),
+                None => {
+                    client.select_by_id(&db_name, &table_name, id).await?;
+                    Err(Denied::new(format!("cannot read {}", id)).into())
+                }
+            }
+        }
+    }
+}
+
+pub trait GetMany<T, Db>
+where
+    T: RestModel,
+    Db: DbClient<T>,
+{
+    /// Fetches `ids` in one round trip. The result lines up with `ids`:
+    /// `None` marks an id with no document, and repeated ids repeat the doc.
+    fn get_many(
+        client: &Db,
+        ids: &[&str],
+    ) -> impl std::future::Future<Output = Result<Vec<Option<Doc<T>>>, Error>> {
+        async {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            let mut unique = ids.to_vec();
+            unique.sort_unstable();
+            unique.dedup();
+            let docs = client.select_by_ids(&db_name, &table_name, &unique).await?;
+            let docs: HashMap<&str, &Doc<T>> =
+                docs.iter().map(|doc| (doc._id.as_str(), doc)).collect();
+            Ok(ids
+                .iter()
+                .map(|id| docs.get(id).map(|doc| (*doc).clone()))
+                .collect())
         }
     }
 }
@@ -42,8 +163,213 @@ where
         pagination_params: &PaginationParams,
     ) -> impl std::future::Future<Output = Result<PaginationResult<T>, Error>> {
         async {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
             client
-                .paginate(T::get_db_name(), T::get_table_name(), pagination_params)
+                .paginate(&db_name, &table_name, pagination_params)
+                .await
+        }
+    }
+
+    /// Like `get`, restricted to `T::read_filter(principal)` and to fields `principal` can read.
+    fn get_as<P>(
+        client: &Db,
+        principal: &P,
+        pagination_params: &PaginationParams,
+    ) -> impl std::future::Future<Output = Result<PaginationResult<Value>, Error>>
+    where
+        T: Policy<P>,
+    {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            T::check_query(principal, pagination_params)?;
+            let extra = T::read_filter(principal);
+            let params = PaginationParams {
+                filter: match pagination_params.filter.clone() {
+                    Some(filter) => Some(restrict(filter, extra)),
+                    None => extra,
+                },
+                ..pagination_params.clone()
+            };
+            let page = client.paginate(&db_name, &table_name, &params).await?;
+            Ok(page.mask(principal)?)
+        }
+    }
+}
+
+pub trait Aggregate<T, Db>
+where
+    T: RestModel,
+    Db: DbClient<T>,
+{
+    fn aggregate(
+        client: &Db,
+        params: &AggregateParams,
+    ) -> impl std::future::Future<Output = Result<Vec<Value>, Error>> {
+        async {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            client.aggregate(&db_name, &table_name, params).await
+        }
+    }
+
+    /// Like `aggregate`, over `T::read_filter(principal)` and only on fields
+    /// `principal` can read.
+    fn aggregate_as<P>(
+        client: &Db,
+        principal: &P,
+        params: &AggregateParams,
+    ) -> impl std::future::Future<Output = Result<Vec<Value>, Error>>
+    where
+        T: Policy<P>,
+    {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            for field in params.fields() {
+                if !T::can_read_field(principal, field) {
+                    return Err(Denied::new(format!("cannot query field {}", field)).into());
+                }
+            }
+            let extra = T::read_filter(principal);
+            let params = AggregateParams {
+                filter: match params.filter.clone() {
+                    Some(filter) => Some(restrict(filter, extra)),
+                    None => extra,
+                },
+                ..params.clone()
+            };
+            client.aggregate(&db_name, &table_name, &params).await
+        }
+    }
+}
+
+pub trait Distinct<T, Db>
+where
+    T: RestModel,
+    Db: DbClient<T>,
+{
+    /// Distinct values of `field`, e.g. `Vec<String>` for a dropdown of statuses.
+    fn distinct<V>(
+        client: &Db,
+        field: &str,
+        filter: Option<&Condition>,
+        limit: Option<u32>,
+    ) -> impl std::future::Future<Output = Result<Vec<V>, Error>>
+    where
+        V: DeserializeOwned,
+    {
+        async move {
+            Self::facets(client, field, filter, limit)
+                .await?
+                .into_iter()
+                .map(|facet: Facet<V>| Ok(facet.value))
+                .collect()
+        }
+    }
+
+    /// Like `distinct`, with the number of documents holding each value.
+    fn facets<V>(
+        client: &Db,
+        field: &str,
+        filter: Option<&Condition>,
+        limit: Option<u32>,
+    ) -> impl std::future::Future<Output = Result<Vec<Facet<V>>, Error>>
+    where
+        V: DeserializeOwned,
+    {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            typed(
+                client
+                    .distinct(&db_name, &table_name, field, filter, limit)
+                    .await?,
+            )
+        }
+    }
+
+    /// Like `facets`, over `T::read_filter(principal)` and only on fields
+    /// `principal` can read.
+    fn facets_as<P, V>(
+        client: &Db,
+        principal: &P,
+        field: &str,
+        filter: Option<&Condition>,
+        limit: Option<u32>,
+    ) -> impl std::future::Future<Output = Result<Vec<Facet<V>>, Error>>
+    where
+        T: Policy<P>,
+        V: DeserializeOwned,
+    {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            let fields = filter.iter().flat_map(|f| f.fields());
+            for field in fields.chain([field]) {
+                if !T::can_read_field(principal, field) {
+                    return Err(Denied::new(format!("cannot query field {}", field)).into());
+                }
+            }
+            let filter = match filter.cloned() {
+                Some(filter) => Some(restrict(filter, T::read_filter(principal))),
+                None => T::read_filter(principal),
+            };
+            typed(
+                client
+                    .distinct(&db_name, &table_name, field, filter.as_ref(), limit)
+                    .await?,
+            )
+        }
+    }
+}
+
+fn typed<V: DeserializeOwned>(facets: Vec<Facet>) -> Result<Vec<Facet<V>>, Error> {
+    facets
+        .into_iter()
+        .map(|facet| {
+            Ok(Facet {
+                value: serde_json::from_value(facet.value)?,
+                count: facet.count,
+            })
+        })
+        .collect()
+}
+
+pub trait Post<T, Db>
+where
+    T: RestModel,
+    Db: DbClient<T>,
+{
+    /// Creates `items` without touching existing documents; see `OnConflict`.
+    fn post(
+        client: &Db,
+        items: &[Doc<T>],
+        on_conflict: OnConflict,
+    ) -> impl std::future::Future<Output = Result<InsertResult, Error>> {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            client
+                .insert(&db_name, &table_name, items, on_conflict)
+                .await
+        }
+    }
+
+    /// Like `post`, but every document must pass `T::check_write(principal, ..)`
+    /// and leave the fields `principal` cannot write unset.
+    fn post_as<P>(
+        client: &Db,
+        principal: &P,
+        items: &[Doc<T>],
+        on_conflict: OnConflict,
+    ) -> impl std::future::Future<Output = Result<InsertResult, Error>>
+    where
+        T: Policy<P>,
+    {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            let guard = T::write_guard(principal);
+            for doc in items {
+                T::check_write(principal, doc)?;
+                guard.check_new(&serde_json::to_value(&doc.data)?)?;
+            }
+            client
+                .insert(&db_name, &table_name, items, on_conflict)
                 .await
         }
     }
@@ -57,13 +383,89 @@ where
     fn put(
         client: &Db,
         items: &[Doc<T>],
-    ) -> impl std::future::Future<Output = Result<UpsertResult, Error>> {
+    ) -> impl std::future::Future<Output = Result<UpsertResult<T>, Error>> {
+        Self::put_returning(client, items, Returning::None)
+    }
+
+    /// Like `put`, also reporting the written ids or documents per `returning`.
+    fn put_returning(
+        client: &Db,
+        items: &[Doc<T>],
+        returning: Returning,
+    ) -> impl std::future::Future<Output = Result<UpsertResult<T>, Error>> {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            client.upsert(&db_name, &table_name, items, returning).await
+        }
+    }
+
+    /// Like `put`, but only overwrites: fails with `Missing` and writes
+    /// nothing if any of `items` doesn't exist yet.
+    fn replace(
+        client: &Db,
+        items: &[Doc<T>],
+    ) -> impl std::future::Future<Output = Result<ReplaceResult, Error>> {
         async {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            client.replace(&db_name, &table_name, items).await
+        }
+    }
+
+    /// Like `put`, but every document must pass `T::check_write(principal, ..)`,
+    /// existing rows outside `T::update_filter(principal)` are not overwritten,
+    /// and the fields `principal` cannot write are kept; see `Policy::write_guard`.
+    fn put_as<P>(
+        client: &Db,
+        principal: &P,
+        items: &[Doc<T>],
+    ) -> impl std::future::Future<Output = Result<UpsertResult<T>, Error>>
+    where
+        T: Policy<P>,
+    {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            for doc in items {
+                T::check_write(principal, doc)?;
+            }
             client
-                .upsert(T::get_db_name(), T::get_table_name(), items)
+                .upsert_guarded(&db_name, &table_name, items, &T::write_guard(principal))
                 .await
         }
     }
+
+    /// Like `replace`, but every document must pass `T::check_write(principal, ..)`
+    /// and the fields `principal` cannot write keep their stored values.
+    fn replace_as<P>(
+        client: &Db,
+        principal: &P,
+        items: &[Doc<T>],
+    ) -> impl std::future::Future<Output = Result<ReplaceResult, Error>>
+    where
+        T: Policy<P>,
+    {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            for doc in items {
+                T::check_write(principal, doc)?;
+            }
+            let guard = WriteGuard {
+                replace_only: true,
+                ..T::write_guard(principal)
+            };
+            let result = client
+                .upsert_guarded(&db_name, &table_name, items, &guard)
+                .await?;
+            let mut seen = HashSet::new();
+            Ok(ReplaceResult {
+                replaced_count: result.updated_count,
+                replaced_ids: items
+                    .iter()
+                    .filter(|doc| seen.insert(doc._id.as_str()))
+                    .map(|doc| doc._id.clone())
+                    .collect(),
+            })
+        }
+    }
 }
 
 pub trait Patch<T, Db>
@@ -74,10 +476,45 @@ where
     fn patch(
         client: &Db,
         params: &PatchParams,
-    ) -> impl std::future::Future<Output = Result<UpdateResult, Error>> {
+    ) -> impl std::future::Future<Output = Result<UpdateResult<T>, Error>> {
+        Self::patch_returning(client, params, Returning::None)
+    }
+
+    /// Like `patch`, also reporting the patched ids or documents per `returning`.
+    fn patch_returning(
+        client: &Db,
+        params: &PatchParams,
+        returning: Returning,
+    ) -> impl std::future::Future<Output = Result<UpdateResult<T>, Error>> {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            client
+                .update(&db_name, &table_name, params, returning)
+                .await
+        }
+    }
+
+    /// Like `patch`, restricted to `T::update_filter(principal)` after `T::check_filter`,
+    /// `T::check_patch` and `T::check_patch_fields`.
+    fn patch_as<P>(
+        client: &Db,
+        principal: &P,
+        params: &PatchParams,
+    ) -> impl std::future::Future<Output = Result<UpdateResult<T>, Error>>
+    where
+        T: Policy<P>,
+    {
         async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            T::check_filter(principal, &params.filter)?;
+            T::check_patch(principal, &params.patch)?;
+            T::check_patch_fields(principal, &params.patch)?;
+            let params = PatchParams {
+                filter: restrict(params.filter.clone(), T::update_filter(principal)),
+                patch: params.patch.clone(),
+            };
             client
-                .update(T::get_db_name(), T::get_table_name(), &params)
+                .update(&db_name, &table_name, &params, Returning::None)
                 .await
         }
     }
@@ -91,10 +528,41 @@ where
     fn delete(
         client: &Db,
         params: &DeleteParams,
-    ) -> impl std::future::Future<Output = Result<DeleteResult, Error>> {
+    ) -> impl std::future::Future<Output = Result<DeleteResult<T>, Error>> {
+        Self::delete_returning(client, params, Returning::None)
+    }
+
+    /// Like `delete`, also reporting the deleted ids or documents per `returning`.
+    fn delete_returning(
+        client: &Db,
+        params: &DeleteParams,
+        returning: Returning,
+    ) -> impl std::future::Future<Output = Result<DeleteResult<T>, Error>> {
+        async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            client
+                .delete(&db_name, &table_name, params, returning)
+                .await
+        }
+    }
+
+    /// Like `delete`, restricted to `T::delete_filter(principal)` after `T::check_filter`.
+    fn delete_as<P>(
+        client: &Db,
+        principal: &P,
+        params: &DeleteParams,
+    ) -> impl std::future::Future<Output = Result<DeleteResult<T>, Error>>
+    where
+        T: Policy<P>,
+    {
         async move {
+            let (db_name, table_name) = client.resolve(T::get_db_name(), T::get_table_name())?;
+            T::check_filter(principal, &params.filter)?;
+            let params = DeleteParams {
+                filter: restrict(params.filter.clone(), T::delete_filter(principal)),
+            };
             client
-                .delete(T::get_db_name(), T::get_table_name(), &params)
+                .delete(&db_name, &table_name, &params, Returning::None)
                 .await
         }
     }
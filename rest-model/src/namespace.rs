@@ -0,0 +1,68 @@
+use anyhow::{bail, Result};
+
+/// Runtime override of where a model's table lives.
+///
+/// `RestModel::get_db_name()`/`get_table_name()` are fixed at compile time; a
+/// `Namespace` held by the client rewrites them per call, so the same model can
+/// address `tenant_a.guest` and `tenant_b.guest`.
+#[derive(Debug, Clone, Default)]
+pub struct Namespace {
+    /// Replaces the schema, i.e. the last segment of `db_name`
+    /// (`mydb.public` -> `mydb.tenant_a`).
+    pub schema: Option<String>,
+    /// Prepended to the table name (`tenant_a_` + `guest`).
+    pub table_prefix: Option<String>,
+}
+
+impl Namespace {
+    pub fn with_schema(schema: &str) -> Result<Self> {
+        check_identifier(schema)?;
+        Ok(Self {
+            schema: Some(schema.to_string()),
+            table_prefix: None,
+        })
+    }
+
+    pub fn with_table_prefix(table_prefix: &str) -> Result<Self> {
+        check_identifier(table_prefix)?;
+        Ok(Self {
+            schema: None,
+            table_prefix: Some(table_prefix.to_string()),
+        })
+    }
+
+    /// Returns the `(db_name, table_name)` pair to use, all segments validated.
+    pub fn resolve(&self, db_name: &str, table_name: &str) -> Result<(String, String)> {
+        let mut segments = db_name.split('.').collect::<Vec<_>>();
+        if let Some(schema) = &self.schema {
+            if let Some(last) = segments.last_mut() {
+                *last = schema;
+            }
+        }
+        for segment in &segments {
+            check_identifier(segment)?;
+        }
+        let table_name = match &self.table_prefix {
+            Some(prefix) => format!("{}{}", prefix, table_name),
+            None => table_name.to_string(),
+        };
+        check_identifier(&table_name)?;
+        Ok((segments.join("."), table_name))
+    }
+}
+
+/// Accepts `[A-Za-z_][A-Za-z0-9_]*` up to 63 bytes, the unquoted identifiers
+/// every supported backend agrees on.
+pub fn check_identifier(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    };
+    if !valid || name.len() > 63 {
+        bail!("Invalid identifier `{}`", name);
+    }
+    Ok(())
+}
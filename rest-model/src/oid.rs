@@ -25,6 +25,12 @@ pub struct ObjectId {
     id: [u8; 12],
 }
 
+impl Default for ObjectId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ObjectId {
     /// Generates a new [`ObjectId`], represented in bytes.
     /// See the [docs](http://www.mongodb.com/docs/manual/reference/object-id/)
@@ -0,0 +1,396 @@
+use serde_json::{json, Map, Value};
+
+use crate::{
+    pagination::{
+        HEADER_CURRENT_PAGE, HEADER_ITEMS_PER_PAGE, HEADER_TOTAL_COUNT, HEADER_TOTAL_PAGES,
+    },
+    ModelSchema, Operation,
+};
+
+/// OpenAPI 3.1 document for one or more `#[rest_model]` types.
+///
+/// Each model contributes exactly the methods enabled via `with(..)`, under the
+/// paths described in the README (`/resources` and `/resources/{id}`).
+pub struct OpenApi {
+    title: String,
+    version: String,
+    paths: Map<String, Value>,
+    schemas: Map<String, Value>,
+}
+
+impl OpenApi {
+    pub fn new(title: &str, version: &str) -> Self {
+        let mut schemas = Map::new();
+        schemas.insert("Condition".to_string(), condition_schema());
+        schemas.insert(
+            "PatchParams".to_string(),
+            object(&[
+                ("filter", reference("Condition")),
+                ("patch", json!({ "type": "object" })),
+            ]),
+        );
+        schemas.insert(
+            "DeleteParams".to_string(),
+            object(&[("filter", reference("Condition"))]),
+        );
+        schemas.insert(
+            "UpsertResult".to_string(),
+            returned(object(&[
+                ("created_count", json!({ "type": "integer", "minimum": 0 })),
+                ("updated_count", json!({ "type": "integer", "minimum": 0 })),
+            ])),
+        );
+        schemas.insert(
+            "InsertResult".to_string(),
+            object(&[
+                ("created_count", json!({ "type": "integer", "minimum": 0 })),
+                (
+                    "created_ids",
+                    json!({ "type": "array", "items": { "type": "string" } }),
+                ),
+                (
+                    "skipped_ids",
+                    json!({ "type": "array", "items": { "type": "string" } }),
+                ),
+            ]),
+        );
+        schemas.insert(
+            "Conflict".to_string(),
+            object(&[(
+                "ids",
+                json!({ "type": "array", "items": { "type": "string" } }),
+            )]),
+        );
+        schemas.insert(
+            "UpdateResult".to_string(),
+            returned(object(&[(
+                "updated_count",
+                json!({ "type": "integer", "minimum": 0 }),
+            )])),
+        );
+        let mut delete_result = returned(object(&[(
+            "deleted_count",
+            json!({ "type": "integer", "minimum": 0 }),
+        )]));
+        let counts = json!({
+            "type": "object",
+            "additionalProperties": { "type": "integer", "minimum": 0 },
+        });
+        delete_result["properties"]["cascaded"] = counts.clone();
+        delete_result["properties"]["nullified"] = counts;
+        schemas.insert("DeleteResult".to_string(), delete_result);
+        Self {
+            title: title.to_string(),
+            version: version.to_string(),
+            paths: Map::new(),
+            schemas,
+        }
+    }
+
+    /// Adds `T` under `path`, e.g. `/guests`.
+    pub fn model<T: ModelSchema>(mut self, path: &str) -> Self {
+        let name = T::get_model_name();
+        let doc_name = format!("{}Doc", name);
+        self.schemas.insert(name.to_string(), T::get_data_schema());
+        let mut doc = object(&[
+            ("_id", json!({ "type": "string" })),
+            ("data", reference(name)),
+            (
+                "_created_at",
+                json!({ "type": "integer", "format": "int64" }),
+            ),
+            (
+                "_updated_at",
+                json!({ "type": "integer", "format": "int64" }),
+            ),
+        ]);
+        // optional, only present when `expand` is requested
+        doc["properties"]["_expanded"] = json!({
+            "type": "object",
+            "additionalProperties": { "type": ["object", "null"] },
+        });
+        // optional, only present when searching with `q`
+        doc["properties"]["_highlights"] = json!({ "type": "object" });
+        self.schemas.insert(doc_name.clone(), doc);
+
+        let path = path.trim_end_matches('/');
+        let mut collection = Map::new();
+        let mut item = Map::new();
+        for operation in T::get_operations() {
+            match operation {
+                Operation::Get => {
+                    collection.insert(
+                        "get".to_string(),
+                        json!({
+                            "operationId": format!("get{}", name),
+                            "tags": [name],
+                            "parameters": pagination_parameters(),
+                            "responses": {
+                                "200": {
+                                    "description": "One page of documents; the headers tell where it lies",
+                                    "headers": pagination_headers(),
+                                    "content": json_content(
+                                        json!({ "type": "array", "items": reference(&doc_name) }),
+                                    ),
+                                },
+                            },
+                        }),
+                    );
+                }
+                Operation::GetWithId => {
+                    item.insert(
+                        "get".to_string(),
+                        json!({
+                            "operationId": format!("get{}WithId", name),
+                            "tags": [name],
+                            "parameters": [{
+                                "name": "id",
+                                "in": "path",
+                                "required": true,
+                                "schema": { "type": "string" },
+                            }],
+                            "responses": {
+                                "200": {
+                                    "description": "The document",
+                                    "content": json_content(reference(&doc_name)),
+                                },
+                                "404": { "description": "Document not found" },
+                            },
+                        }),
+                    );
+                }
+                Operation::Post => {
+                    collection.insert(
+                        "post".to_string(),
+                        json!({
+                            "operationId": format!("post{}", name),
+                            "tags": [name],
+                            "parameters": [{
+                                "name": "on_conflict",
+                                "in": "query",
+                                "description": "`fail` (default) rejects the whole request if any id exists, `skip` leaves existing documents untouched",
+                                "schema": { "type": "string", "enum": ["fail", "skip"] },
+                            }],
+                            "requestBody": {
+                                "required": true,
+                                "content": json_content(
+                                    json!({ "type": "array", "items": reference(&doc_name) }),
+                                ),
+                            },
+                            "responses": {
+                                "200": {
+                                    "description": "Documents created",
+                                    "content": json_content(reference("InsertResult")),
+                                },
+                                "409": {
+                                    "description": "Some ids already exist; nothing was written",
+                                    "content": json_content(reference("Conflict")),
+                                },
+                            },
+                        }),
+                    );
+                }
+                Operation::Put => {
+                    collection.insert(
+                        "put".to_string(),
+                        json!({
+                            "operationId": format!("put{}", name),
+                            "tags": [name],
+                            "parameters": [returning_parameter()],
+                            "requestBody": {
+                                "required": true,
+                                "content": json_content(
+                                    json!({ "type": "array", "items": reference(&doc_name) }),
+                                ),
+                            },
+                            "responses": {
+                                "200": {
+                                    "description": "Documents created or replaced",
+                                    "content": json_content(reference("UpsertResult")),
+                                },
+                            },
+                        }),
+                    );
+                }
+                Operation::Patch => {
+                    collection.insert(
+                        "patch".to_string(),
+                        json!({
+                            "operationId": format!("patch{}", name),
+                            "tags": [name],
+                            "parameters": [returning_parameter()],
+                            "requestBody": {
+                                "required": true,
+                                "content": json_content(reference("PatchParams")),
+                            },
+                            "responses": {
+                                "200": {
+                                    "description": "Documents matching the filter were patched",
+                                    "content": json_content(reference("UpdateResult")),
+                                },
+                            },
+                        }),
+                    );
+                }
+                Operation::Delete => {
+                    collection.insert(
+                        "delete".to_string(),
+                        json!({
+                            "operationId": format!("delete{}", name),
+                            "tags": [name],
+                            "parameters": [returning_parameter()],
+                            "requestBody": {
+                                "required": true,
+                                "content": json_content(reference("DeleteParams")),
+                            },
+                            "responses": {
+                                "200": {
+                                    "description": "Documents matching the filter were deleted",
+                                    "content": json_content(reference("DeleteResult")),
+                                },
+                            },
+                        }),
+                    );
+                }
+            }
+        }
+        if !collection.is_empty() {
+            self.paths
+                .insert(path.to_string(), Value::Object(collection));
+        }
+        if !item.is_empty() {
+            self.paths
+                .insert(format!("{}/{{id}}", path), Value::Object(item));
+        }
+        self
+    }
+
+    pub fn build(self) -> Value {
+        json!({
+            "openapi": "3.1.0",
+            "info": { "title": self.title, "version": self.version },
+            "paths": self.paths,
+            "components": { "schemas": self.schemas },
+        })
+    }
+}
+
+/// OpenAPI document for a single model served at `/{table_name}`.
+pub fn openapi<T: ModelSchema>(title: &str, version: &str) -> Value {
+    OpenApi::new(title, version)
+        .model::<T>(&format!("/{}", T::get_table_name()))
+        .build()
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn json_content(schema: Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+fn object(properties: &[(&str, Value)]) -> Value {
+    let required = properties.iter().map(|(k, _)| *k).collect::<Vec<_>>();
+    let properties = properties
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect::<Map<_, _>>();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+/// Adds the `ids` and `docs` a write fills in when asked via `?returning=`.
+fn returned(mut schema: Value) -> Value {
+    let properties = schema["properties"].as_object_mut().unwrap();
+    properties.insert(
+        "ids".to_string(),
+        json!({ "type": "array", "items": { "type": "string" } }),
+    );
+    properties.insert(
+        "docs".to_string(),
+        json!({ "type": "array", "items": { "type": "object" } }),
+    );
+    schema
+}
+
+fn returning_parameter() -> Value {
+    json!({
+        "name": "returning",
+        "in": "query",
+        "description": "`ids` or `docs` to get the affected documents back",
+        "schema": { "type": "string", "enum": ["none", "ids", "docs"] },
+    })
+}
+
+/// `PaginationParams` as query parameters; `filter` is a JSON-encoded `Condition`.
+fn pagination_parameters() -> Value {
+    json!([
+        { "name": "page", "in": "query", "schema": { "type": "integer", "minimum": 1 } },
+        { "name": "limit", "in": "query", "schema": { "type": "integer", "minimum": 1 } },
+        {
+            "name": "sort",
+            "in": "query",
+            "description": "Field paths prefixed with `+` (ascending) or `-` (descending), each optionally followed by `:text`, `:number` or `:timestamp` and `:nulls_first` or `:nulls_last`, e.g. `+name-age:number`; `-_rank` orders a search by relevance",
+            "schema": { "type": "string" },
+        },
+        {
+            "name": "filter",
+            "in": "query",
+            "content": json_content(reference("Condition")),
+        },
+        { "name": "custom", "in": "query", "schema": { "type": "string" } },
+        {
+            "name": "expand",
+            "in": "query",
+            "description": "Comma-separated reference fields whose documents to embed in `_expanded`",
+            "schema": { "type": "string" },
+        },
+        {
+            "name": "q",
+            "in": "query",
+            "description": "Full-text search of the model's search fields, e.g. `\"red apple\" or pear -green`, ranked by `_rank`",
+            "schema": { "type": "string" },
+        },
+    ])
+}
+
+fn pagination_headers() -> Value {
+    let header =
+        |description: &str| json!({ "description": description, "schema": { "type": "integer" } });
+    json!({
+        HEADER_TOTAL_COUNT: header("Documents matching the filter"),
+        HEADER_TOTAL_PAGES: header("Pages at the current limit"),
+        HEADER_CURRENT_PAGE: header("The returned page, starting at 1"),
+        HEADER_ITEMS_PER_PAGE: header("The applied limit"),
+    })
+}
+
+/// `Condition` in serde's externally-tagged encoding, e.g. `{"Eq": ["name", "Tom"]}`.
+fn condition_schema() -> Value {
+    let tagged = |tag: &str, value: Value| {
+        json!({
+            "type": "object",
+            "properties": { tag: value },
+            "required": [tag],
+            "additionalProperties": false,
+        })
+    };
+    let list = json!({ "type": "array", "items": reference("Condition") });
+    let pair = json!({
+        "type": "array",
+        "prefixItems": [{ "type": "string" }, {}],
+        "minItems": 2,
+        "maxItems": 2,
+    });
+    let mut variants = vec![
+        tagged("And", list.clone()),
+        tagged("Or", list),
+        tagged("Not", reference("Condition")),
+    ];
+    for tag in [
+        "Regex", "Regexi", "Eq", "Ne", "Gt", "Lt", "Gte", "Lte", "In", "Nin",
+    ] {
+        variants.push(tagged(tag, pair.clone()));
+    }
+    json!({ "oneOf": variants })
+}
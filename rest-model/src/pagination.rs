@@ -1,16 +1,21 @@
+use crate::{Condition, SortType};
 use serde::{Deserialize, Serialize};
-use crate::Condition;
 
-#[derive(Debug, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct PaginationParams {
     pub page: Option<u32>,
     pub limit: Option<u32>,
     pub sort: Option<String>,
     pub filter: Option<Condition>,
     pub custom: Option<String>,
+    /// Comma-separated reference fields whose documents to embed, e.g. `customer_id`.
+    pub expand: Option<String>,
+    /// Full-text search of the model's `search` fields in web search syntax,
+    /// e.g. `"red apple" or pear -green`; sorts by `-_rank` unless `sort` is set.
+    pub q: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Pagination {
     pub total_count: u32,
     pub total_pages: u32,
@@ -18,6 +23,154 @@ pub struct Pagination {
     pub items_per_page: u32,
 }
 
+impl PaginationParams {
+    /// Query string pairs for `GET /resources`; `filter` is JSON-encoded.
+    pub fn to_query(&self) -> Result<Vec<(&'static str, String)>, serde_json::Error> {
+        let mut query = vec![];
+        if let Some(page) = self.page {
+            query.push(("page", page.to_string()));
+        }
+        if let Some(limit) = self.limit {
+            query.push(("limit", limit.to_string()));
+        }
+        if let Some(sort) = &self.sort {
+            query.push(("sort", sort.clone()));
+        }
+        if let Some(filter) = &self.filter {
+            query.push(("filter", serde_json::to_string(filter)?));
+        }
+        if let Some(custom) = &self.custom {
+            query.push(("custom", custom.clone()));
+        }
+        if let Some(expand) = &self.expand {
+            query.push(("expand", expand.clone()));
+        }
+        if let Some(q) = &self.q {
+            query.push(("q", q.clone()));
+        }
+        Ok(query)
+    }
+
+    /// Inverse of `to_query`; unknown keys are ignored.
+    pub fn from_query<'a>(
+        query: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> anyhow::Result<Self> {
+        let mut params = Self::default();
+        for (key, value) in query {
+            match key {
+                "page" => params.page = Some(value.parse()?),
+                "limit" => params.limit = Some(value.parse()?),
+                "sort" => params.sort = Some(value.to_string()),
+                "filter" => params.filter = Some(serde_json::from_str(value)?),
+                "custom" => params.custom = Some(value.to_string()),
+                "expand" => params.expand = Some(value.to_string()),
+                "q" => params.q = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Ok(params)
+    }
+}
+
+impl Pagination {
+    /// The `X-*` response headers carrying this pagination.
+    pub fn to_headers(&self) -> [(&'static str, String); 4] {
+        [
+            (HEADER_TOTAL_COUNT, self.total_count.to_string()),
+            (HEADER_TOTAL_PAGES, self.total_pages.to_string()),
+            (HEADER_CURRENT_PAGE, self.current_page.to_string()),
+            (HEADER_ITEMS_PER_PAGE, self.items_per_page.to_string()),
+        ]
+    }
+
+    /// Inverse of `to_headers`; `get` looks a header up case-insensitively.
+    pub fn from_headers<'a>(get: impl Fn(&str) -> Option<&'a str>) -> anyhow::Result<Self> {
+        let header = |name: &str| -> anyhow::Result<u32> {
+            Ok(get(name)
+                .ok_or_else(|| anyhow::anyhow!("Missing header {}", name))?
+                .parse()?)
+        };
+        Ok(Self {
+            total_count: header(HEADER_TOTAL_COUNT)?,
+            total_pages: header(HEADER_TOTAL_PAGES)?,
+            current_page: header(HEADER_CURRENT_PAGE)?,
+            items_per_page: header(HEADER_ITEMS_PER_PAGE)?,
+        })
+    }
+}
+
+/// The fields of an `expand` list such as `customer_id,product_id`.
+pub fn expand_fields(expand: &str) -> Vec<&str> {
+    expand
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Field paths of a sort expression such as `+name-age`.
+pub fn sort_fields(sort: &str) -> Vec<&str> {
+    sort.split(['+', '-'])
+        .filter(|s| !s.is_empty())
+        .map(|key| key.split(':').next().unwrap_or(key))
+        .collect()
+}
+
+/// One key of a sort expression: `+field` or `-field`, optionally followed by
+/// `:text`, `:number` or `:timestamp` and by `:nulls_first` or `:nulls_last`,
+/// e.g. `-price:number:nulls_last+_created_at`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: String,
+    pub descending: bool,
+    /// How values compare; `None` leaves it to the model's `get_sort_types`.
+    pub sort_type: Option<SortType>,
+    /// Where missing values go; `None` is last ascending and first descending.
+    pub nulls: Option<Nulls>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nulls {
+    First,
+    Last,
+}
+
+/// Parses a sort expression; a key without a `+` sorts descending.
+pub fn parse_sort(sort: &str) -> anyhow::Result<Vec<SortKey>> {
+    let mut keys = vec![];
+    let mut rest = sort;
+    while !rest.is_empty() {
+        let descending = !rest.starts_with('+');
+        rest = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+        let end = rest.find(['+', '-']).unwrap_or(rest.len());
+        let (key, tail) = rest.split_at(end);
+        rest = tail;
+        if key.is_empty() {
+            continue;
+        }
+        let mut parts = key.split(':');
+        let field = parts.next().unwrap_or(key);
+        let mut sort_key = SortKey {
+            field: field.to_string(),
+            descending,
+            sort_type: None,
+            nulls: None,
+        };
+        for modifier in parts {
+            match modifier {
+                "text" => sort_key.sort_type = Some(SortType::Text),
+                "number" => sort_key.sort_type = Some(SortType::Number),
+                "timestamp" => sort_key.sort_type = Some(SortType::Timestamp),
+                "nulls_first" => sort_key.nulls = Some(Nulls::First),
+                "nulls_last" => sort_key.nulls = Some(Nulls::Last),
+                _ => anyhow::bail!("Invalid sort modifier `{}` of {}", modifier, field),
+            }
+        }
+        keys.push(sort_key);
+    }
+    Ok(keys)
+}
+
 pub const HEADER_EXPOSE: &str = "Access-Control-Expose-Headers";
 pub const DEFAULT_PAGE: u32 = 1;
 pub const DEFAULT_LIMIT: u32 = 10;
@@ -1,4 +1,4 @@
-use serde::{Serialize, Deserialize};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,4 +27,26 @@ pub enum Condition {
     Lte(String, Value),
     In(String, Value),
     Nin(String, Value),
-}
\ No newline at end of file
+}
+
+impl Condition {
+    /// Every field path referenced by this condition.
+    pub fn fields(&self) -> Vec<&str> {
+        match self {
+            Condition::And(conds) | Condition::Or(conds) => {
+                conds.iter().flat_map(|c| c.fields()).collect()
+            }
+            Condition::Not(cond) => cond.fields(),
+            Condition::Regex(field, _)
+            | Condition::Regexi(field, _)
+            | Condition::Eq(field, _)
+            | Condition::Ne(field, _)
+            | Condition::Gt(field, _)
+            | Condition::Lt(field, _)
+            | Condition::Gte(field, _)
+            | Condition::Lte(field, _)
+            | Condition::In(field, _)
+            | Condition::Nin(field, _) => vec![field.as_str()],
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use serde_json::{Map, Value};
+
+use crate::{
+    pagination::{sort_fields, PaginationParams},
+    Condition, Denied, Doc, FieldPermission, PaginationResult, RestModel,
+};
+
+/// Row-level authorization for a model, evaluated against a caller-supplied principal `P`.
+///
+/// The `*_as` methods of the `method` traits AND the returned filters into every
+/// query and run the checks before writing, so e.g. "users only see their own
+/// orders" is enforced at the data layer rather than in each handler.
+pub trait Policy<P>: RestModel {
+    /// Extra filter for `get_as` and `get_with_id_as`.
+    fn read_filter(_principal: &P) -> Option<Condition> {
+        None
+    }
+
+    /// Extra filter for `patch_as`; also keeps `put_as` and `replace_as` from
+    /// overwriting rows outside it.
+    fn update_filter(_principal: &P) -> Option<Condition> {
+        None
+    }
+
+    /// Extra filter for `delete_as`.
+    fn delete_filter(_principal: &P) -> Option<Condition> {
+        None
+    }
+
+    /// Checked for every document passed to `post_as`, `put_as` and `replace_as`.
+    fn check_write(_principal: &P, _doc: &Doc<Self>) -> Result<(), Denied> {
+        Ok(())
+    }
+
+    /// Checked for the patch passed to `patch_as`.
+    fn check_patch(_principal: &P, _patch: &Value) -> Result<(), Denied> {
+        Ok(())
+    }
+
+    /// Whether `principal` holds `role`, for the model's field permissions.
+    fn has_role(_principal: &P, _role: &str) -> bool {
+        false
+    }
+
+    fn can_read_field(principal: &P, field: &str) -> bool {
+        field_allowed(
+            Self::get_field_permissions(),
+            field,
+            |p| p.read,
+            |role| Self::has_role(principal, role),
+        )
+    }
+
+    fn can_write_field(principal: &P, field: &str) -> bool {
+        field_allowed(
+            Self::get_field_permissions(),
+            field,
+            |p| p.write,
+            |role| Self::has_role(principal, role),
+        )
+    }
+
+    /// Rejects filters, sorts and searches on fields `principal` cannot read,
+    /// which would otherwise leak hidden values through the result set, and
+    /// any `expand`, which would bypass the referenced model's own policy.
+    fn check_query(principal: &P, params: &PaginationParams) -> Result<(), Denied> {
+        if params.expand.is_some() {
+            return Err(Denied::new("cannot expand references"));
+        }
+        let filter_fields = params.filter.iter().flat_map(|f| f.fields());
+        let sort_fields = params.sort.iter().flat_map(|s| sort_fields(s));
+        let search_fields = match (&params.q, Self::get_search()) {
+            (Some(_), Some(search)) => search.fields,
+            _ => &[],
+        };
+        for field in filter_fields
+            .chain(sort_fields)
+            .chain(search_fields.iter().copied())
+        {
+            if !Self::can_read_field(principal, field) {
+                return Err(Denied::new(format!("cannot query field {}", field)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects filters on fields `principal` cannot read, for `patch_as` and
+    /// `delete_as`, whose counts would otherwise leak the hidden values.
+    fn check_filter(principal: &P, filter: &Condition) -> Result<(), Denied> {
+        for field in filter.fields() {
+            if !Self::can_read_field(principal, field) {
+                return Err(Denied::new(format!("cannot query field {}", field)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects patches touching fields `principal` cannot write.
+    fn check_patch_fields(principal: &P, patch: &Value) -> Result<(), Denied> {
+        if let Some(patch) = patch.as_object() {
+            for field in patch.keys() {
+                if !Self::can_write_field(principal, field) {
+                    return Err(Denied::new(format!("cannot write field {}", field)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `update_filter` and the fields `principal` cannot write, guarding
+    /// `put_as` and `replace_as`.
+    fn write_guard(principal: &P) -> WriteGuard {
+        WriteGuard {
+            filter: Self::update_filter(principal),
+            protected: Self::get_field_permissions()
+                .iter()
+                .filter(|p| !Self::can_write_field(principal, p.field))
+                .map(|p| p.field.to_string())
+                .collect(),
+            replace_only: false,
+        }
+    }
+
+    /// Serializes `doc` without the fields `principal` cannot read.
+    fn mask(principal: &P, doc: &Doc<Self>) -> Result<Doc<Value>, serde_json::Error> {
+        let mut data = serde_json::to_value(&doc.data)?;
+        if let Some(data) = data.as_object_mut() {
+            data.retain(|field, _| Self::can_read_field(principal, field));
+        }
+        Ok(Doc {
+            _id: doc._id.clone(),
+            data,
+            _created_at: doc._created_at,
+            _updated_at: doc._updated_at,
+            _expanded: Map::new(),
+            _highlights: doc
+                ._highlights
+                .iter()
+                .filter(|(field, _)| Self::can_read_field(principal, field))
+                .map(|(field, highlight)| (field.clone(), highlight.clone()))
+                .collect(),
+        })
+    }
+}
+
+impl<T: RestModel> PaginationResult<T> {
+    /// Strips the fields `principal` cannot read from every item.
+    pub fn mask<P>(&self, principal: &P) -> Result<PaginationResult<Value>, serde_json::Error>
+    where
+        T: Policy<P>,
+    {
+        Ok(PaginationResult {
+            items: self
+                .items
+                .iter()
+                .map(|doc| T::mask(principal, doc))
+                .collect::<Result<_, _>>()?,
+            pagination: self.pagination.clone(),
+        })
+    }
+}
+
+/// How `DbClient::upsert_guarded` writes on behalf of a principal.
+#[derive(Debug, Clone, Default)]
+pub struct WriteGuard {
+    /// Existing rows it doesn't match, or can't tell, fail the write with `Denied`.
+    pub filter: Option<Condition>,
+    /// Top-level fields the principal cannot write: a new document setting
+    /// one fails with `Denied`, an overwritten one keeps its stored value.
+    pub protected: Vec<String>,
+    /// Fail with `Missing` instead of creating documents, as `replace` does.
+    pub replace_only: bool,
+}
+
+impl WriteGuard {
+    /// Rejects `data` of a new document that sets a protected field to anything but `null`.
+    pub fn check_new(&self, data: &Value) -> Result<(), Denied> {
+        for field in &self.protected {
+            if data.get(field).is_some_and(|value| !value.is_null()) {
+                return Err(Denied::new(format!("cannot write field {}", field)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A field path is governed by the permission of its first segment.
+fn field_allowed(
+    permissions: &[FieldPermission],
+    field: &str,
+    roles: impl Fn(&FieldPermission) -> Option<&'static [&'static str]>,
+    has_role: impl Fn(&str) -> bool,
+) -> bool {
+    let top = field.split('.').next().unwrap_or(field);
+    match permissions.iter().find(|p| p.field == top).and_then(roles) {
+        Some(allowed) => allowed.iter().any(|role| has_role(role)),
+        None => true,
+    }
+}
+
+/// `filter AND extra`, or `filter` when there is no extra condition.
+pub fn restrict(filter: Condition, extra: Option<Condition>) -> Condition {
+    match extra {
+        Some(extra) => Condition::And(vec![Box::new(filter), Box::new(extra)]),
+        None => filter,
+    }
+}
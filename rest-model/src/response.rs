@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::{pagination::Pagination, Doc};
@@ -8,18 +10,74 @@ pub struct PaginationResult<T: Serialize> {
     pub pagination: Pagination,
 }
 
+/// What a write reports back about the documents it touched, besides counts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Returning {
+    #[default]
+    None,
+    /// Fill `ids`.
+    Ids,
+    /// Fill `ids` and `docs`, the documents as written (or as they were, for deletes).
+    Docs,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-pub struct UpdateResult {
+pub struct UpdateResult<T> {
     pub updated_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub docs: Option<Vec<Doc<T>>>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct UpsertResult {
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpsertResult<T> {
     pub created_count: u32,
     pub updated_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub docs: Option<Vec<Doc<T>>>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct DeleteResult {
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteResult<T> {
     pub deleted_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub docs: Option<Vec<Doc<T>>>,
+    /// Documents deleted by `cascade` references, by the model's table name,
+    /// without the client's `Namespace` applied.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub cascaded: HashMap<String, u32>,
+    /// Documents whose `set_null` reference was cleared, by the model's
+    /// table name like `cascaded`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub nullified: HashMap<String, u32>,
+}
+
+/// What `insert` does with a document whose `_id` already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnConflict {
+    /// Write nothing and return `Conflict` listing the existing ids.
+    #[default]
+    Fail,
+    /// Insert the rest and report the existing ids in `skipped_ids`.
+    Skip,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InsertResult {
+    pub created_count: u32,
+    pub created_ids: Vec<String>,
+    pub skipped_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplaceResult {
+    pub replaced_count: u32,
+    pub replaced_ids: Vec<String>,
 }
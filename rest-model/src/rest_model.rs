@@ -6,4 +6,118 @@ impl<T> RestModelBound for T where T: Clone + Send + Sync + Serialize + for<'de>
 pub trait RestModel: RestModelBound {
     fn get_db_name() -> &'static str;
     fn get_table_name() -> &'static str;
+
+    /// Per-field role restrictions, generated from `#[field(read(..), write(..))]`.
+    fn get_field_permissions() -> &'static [FieldPermission] {
+        &[]
+    }
+
+    /// Fields holding another model's `_id`, generated from `ref(field -> Model)`.
+    fn get_references() -> &'static [Reference] {
+        &[]
+    }
+
+    /// Models whose references point at this one, generated from `referenced_by(Model)`;
+    /// their `on_delete` is applied when documents of this model are deleted.
+    fn get_referrers() -> &'static [Referrer] {
+        &[]
+    }
+
+    /// How top-level fields compare when sorted, generated from the field
+    /// types and `#[field(sort(..))]`; unlisted fields sort as text.
+    fn get_sort_types() -> &'static [FieldSort] {
+        &[]
+    }
+
+    /// Fields searched by `PaginationParams::q`, generated from `search(..)`.
+    fn get_search() -> Option<SearchConfig> {
+        None
+    }
+}
+
+/// Roles allowed to read or write one top-level field of a model's `data`.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldPermission {
+    pub field: &'static str,
+    /// `None` lets everyone read the field.
+    pub read: Option<&'static [&'static str]>,
+    /// `None` lets everyone write the field.
+    pub write: Option<&'static [&'static str]>,
+}
+
+/// How values of a field compare in a sort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortType {
+    /// As strings.
+    Text,
+    /// As numbers, with non-numeric values sorted as missing.
+    Number,
+    /// As points in time: RFC 3339 strings or milliseconds since the epoch,
+    /// like `_created_at`.
+    Timestamp,
+}
+
+/// The `SortType` of one top-level field of a model's `data`.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldSort {
+    pub field: &'static str,
+    pub sort_type: SortType,
 }
+
+/// Full-text search over top-level fields of `data`, declared with
+/// `search(name, description, tags, language = "english")`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchConfig {
+    /// String or array-of-strings fields, most relevant first: matches in the
+    /// first, second and third rank above matches in the rest.
+    pub fields: &'static [&'static str],
+    /// Text search configuration stemming words, `english` by default.
+    pub language: &'static str,
+}
+
+/// A top-level field of `data` holding the `_id` of a document in another model's table.
+#[derive(Debug, Clone, Copy)]
+pub struct Reference {
+    pub field: &'static str,
+    /// The referenced model's `RestModel::get_db_name`.
+    pub db_name: fn() -> &'static str,
+    /// The referenced model's `RestModel::get_table_name`.
+    pub table_name: fn() -> &'static str,
+    pub on_delete: OnDelete,
+}
+
+/// What deleting a referenced document does to the documents referencing it,
+/// set with `ref(field -> Model(cascade))`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnDelete {
+    /// Refuse the delete with `Referenced`.
+    #[default]
+    Restrict,
+    /// Delete the referencing documents too.
+    Cascade,
+    /// Set the reference field to `null`.
+    SetNull,
+}
+
+/// A model that may reference this one, as listed in `referenced_by(..)`.
+#[derive(Debug, Clone, Copy)]
+pub struct Referrer {
+    pub db_name: fn() -> &'static str,
+    pub table_name: fn() -> &'static str,
+    pub references: fn() -> &'static [Reference],
+    /// The referring model's own referrers, followed through cascades.
+    pub referrers: fn() -> &'static [Referrer],
+}
+
+/// Implemented by `referenced_by(R)`. Every `ref(field -> Self)` in `R`
+/// requires it, since deletes only enforce `on_delete` for the referrers a
+/// model lists.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is referenced by `{R}` but doesn't list it in `referenced_by(..)`",
+    label = "add `referenced_by({R})` to `{Self}`"
+)]
+pub trait ReferencedBy<R> {}
+
+/// Fails to compile unless `M: ReferencedBy<R>`; emitted for each `ref(..)`.
+#[doc(hidden)]
+pub fn assert_referenced_by<M: ReferencedBy<R>, R>() {}
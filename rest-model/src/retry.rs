@@ -0,0 +1,409 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use serde_json::Value;
+
+use crate::{
+    pagination::PaginationParams, AggregateParams, CircuitOpen, Condition, DbClient, DeleteParams,
+    DeleteResult, Doc, Facet, InsertResult, OnConflict, PaginationResult, PatchParams,
+    ReplaceResult, RestModel, Returning, SearchConfig, UpdateResult, UpsertResult, WriteGuard,
+};
+
+/// Why a failed call may succeed if tried again; see `DbClient::transient`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transient {
+    /// The call had no effect, e.g. its transaction was rolled back after a
+    /// serialization failure or deadlock, or no connection could be had.
+    /// Any operation may be retried.
+    NotApplied,
+    /// The connection broke mid-call, which may or may not have taken effect.
+    /// Only idempotent operations are retried.
+    MaybeApplied,
+}
+
+/// Exponential backoff with full jitter: the n-th retry waits a random time
+/// up to `base_delay * 2^(n-1)`, capped at `max_delay`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts per call, including the first; 1 disables retries.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn delay(&self, retry: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(1 << (retry - 1).min(31))
+            .min(self.max_delay);
+        exp.mul_f64(rand::random::<f64>())
+    }
+}
+
+/// Fails calls fast once `failure_threshold` calls in a row ended in a
+/// transient error, until `reset_timeout` has passed. Then calls go through
+/// again; the first success closes the breaker, a transient failure reopens it.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    pub failure_threshold: u32,
+    pub reset_timeout: Duration,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Wraps any `DbClient` to retry calls that failed with a `Transient` error,
+/// as classified by the wrapped client, and optionally guard them with a
+/// `CircuitBreaker`:
+///
+/// ```ignore
+/// let client = RetryDb::new(Db::try_new(uri).await?)
+///     .with_retry(RetryPolicy { max_attempts: 5, ..Default::default() })
+///     .with_circuit_breaker(CircuitBreaker::default());
+/// ```
+///
+/// `insert` and `copy_in` are not idempotent, so they are only retried when
+/// the error is `Transient::NotApplied`. An open breaker fails calls with
+/// `CircuitOpen`.
+pub struct RetryDb<D> {
+    inner: D,
+    retry: RetryPolicy,
+    breaker: Option<(CircuitBreaker, Mutex<BreakerState>)>,
+}
+
+impl<D> RetryDb<D> {
+    /// Retries with the default `RetryPolicy` and no circuit breaker.
+    pub fn new(inner: D) -> Self {
+        Self {
+            inner,
+            retry: RetryPolicy::default(),
+            breaker: None,
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = Some((breaker, Mutex::new(BreakerState::default())));
+        self
+    }
+
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Whether the circuit breaker currently fails calls fast.
+    pub fn is_open(&self) -> bool {
+        self.check_breaker().is_err()
+    }
+
+    fn check_breaker(&self) -> Result<(), CircuitOpen> {
+        let Some((breaker, state)) = &self.breaker else {
+            return Ok(());
+        };
+        let state = state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.opened_at {
+            Some(opened_at) if opened_at.elapsed() < breaker.reset_timeout => Err(CircuitOpen {
+                retry_after: breaker.reset_timeout - opened_at.elapsed(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn record(&self, transient_failure: bool) {
+        let Some((breaker, state)) = &self.breaker else {
+            return;
+        };
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        if transient_failure {
+            state.failures += 1;
+            if state.failures >= breaker.failure_threshold {
+                state.opened_at = Some(Instant::now());
+            }
+        } else {
+            *state = BreakerState::default();
+        }
+    }
+
+    async fn call<T, R>(
+        &self,
+        idempotent: bool,
+        mut attempt: impl AsyncFnMut() -> Result<R, Error>,
+    ) -> Result<R, Error>
+    where
+        T: RestModel,
+        D: DbClient<T>,
+    {
+        self.check_breaker()?;
+        let mut attempts = 1;
+        loop {
+            let result = attempt().await;
+            let transient = match &result {
+                Ok(_) => None,
+                Err(e) => self.inner.transient(e),
+            };
+            let retry = match transient {
+                Some(Transient::NotApplied) => true,
+                Some(Transient::MaybeApplied) => idempotent,
+                None => false,
+            };
+            if retry && attempts < self.retry.max_attempts {
+                tokio::time::sleep(self.retry.delay(attempts)).await;
+                attempts += 1;
+                continue;
+            }
+            self.record(transient.is_some());
+            return result;
+        }
+    }
+}
+
+impl<T, D> DbClient<T> for RetryDb<D>
+where
+    T: RestModel,
+    D: DbClient<T>,
+{
+    fn generate_id(&self) -> String {
+        self.inner.generate_id()
+    }
+
+    fn resolve(&self, db_name: &str, table_name: &str) -> Result<(String, String), Error> {
+        self.inner.resolve(db_name, table_name)
+    }
+
+    fn transient(&self, error: &Error) -> Option<Transient> {
+        self.inner.transient(error)
+    }
+
+    fn data_error(&self, error: &Error) -> bool {
+        self.inner.data_error(error)
+    }
+
+    async fn init(&self, db_name: &str, table_name: &str) -> Result<(), Error> {
+        self.call(true, async || self.inner.init(db_name, table_name).await)
+            .await
+    }
+
+    async fn install_schema(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        schema: &Value,
+    ) -> Result<(), Error> {
+        self.call(true, async || {
+            self.inner.install_schema(db_name, table_name, schema).await
+        })
+        .await
+    }
+
+    async fn install_search(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        search: &SearchConfig,
+    ) -> Result<(), Error> {
+        self.call(true, async || {
+            self.inner.install_search(db_name, table_name, search).await
+        })
+        .await
+    }
+
+    async fn select_by_id(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        id: &str,
+    ) -> Result<Doc<T>, Error> {
+        self.call(true, async || {
+            self.inner.select_by_id(db_name, table_name, id).await
+        })
+        .await
+    }
+
+    async fn select_by_ids(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        ids: &[&str],
+    ) -> Result<Vec<Doc<T>>, Error> {
+        self.call(true, async || {
+            self.inner.select_by_ids(db_name, table_name, ids).await
+        })
+        .await
+    }
+
+    async fn expand(&self, docs: &mut [Doc<T>], fields: &[&str]) -> Result<(), Error> {
+        self.call(true, async || self.inner.expand(docs, fields).await)
+            .await
+    }
+
+    async fn paginate(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        pagination_params: &PaginationParams,
+    ) -> Result<PaginationResult<T>, Error> {
+        self.call(true, async || {
+            self.inner
+                .paginate(db_name, table_name, pagination_params)
+                .await
+        })
+        .await
+    }
+
+    async fn aggregate(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        params: &AggregateParams,
+    ) -> Result<Vec<Value>, Error> {
+        self.call(true, async || {
+            self.inner.aggregate(db_name, table_name, params).await
+        })
+        .await
+    }
+
+    async fn distinct(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        field: &str,
+        filter: Option<&Condition>,
+        limit: Option<u32>,
+    ) -> Result<Vec<Facet>, Error> {
+        self.call(true, async || {
+            self.inner
+                .distinct(db_name, table_name, field, filter, limit)
+                .await
+        })
+        .await
+    }
+
+    async fn upsert(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        returning: Returning,
+    ) -> Result<UpsertResult<T>, Error> {
+        self.call(true, async || {
+            self.inner
+                .upsert(db_name, table_name, items, returning)
+                .await
+        })
+        .await
+    }
+
+    async fn insert(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        on_conflict: OnConflict,
+    ) -> Result<InsertResult, Error> {
+        self.call(false, async || {
+            self.inner
+                .insert(db_name, table_name, items, on_conflict)
+                .await
+        })
+        .await
+    }
+
+    async fn replace(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+    ) -> Result<ReplaceResult, Error> {
+        self.call(true, async || {
+            self.inner.replace(db_name, table_name, items).await
+        })
+        .await
+    }
+
+    async fn upsert_guarded(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+        guard: &WriteGuard,
+    ) -> Result<UpsertResult<T>, Error> {
+        self.call(true, async || {
+            self.inner
+                .upsert_guarded(db_name, table_name, items, guard)
+                .await
+        })
+        .await
+    }
+
+    async fn copy_in(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        items: &[Doc<T>],
+    ) -> Result<u32, Error> {
+        self.call(false, async || {
+            self.inner.copy_in(db_name, table_name, items).await
+        })
+        .await
+    }
+
+    async fn update(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        params: &PatchParams,
+        returning: Returning,
+    ) -> Result<UpdateResult<T>, Error> {
+        self.call(true, async || {
+            self.inner
+                .update(db_name, table_name, params, returning)
+                .await
+        })
+        .await
+    }
+
+    async fn delete(
+        &self,
+        db_name: &str,
+        table_name: &str,
+        filter: &DeleteParams,
+        returning: Returning,
+    ) -> Result<DeleteResult<T>, Error> {
+        self.call(true, async || {
+            self.inner
+                .delete(db_name, table_name, filter, returning)
+                .await
+        })
+        .await
+    }
+}
@@ -0,0 +1,39 @@
+use serde_json::{json, Value};
+
+use crate::RestModel;
+
+/// A REST method a model enables through `#[rest_model(with(..))]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Get,
+    GetWithId,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+/// Compile-time description of a model, generated by `#[rest_model]`.
+pub trait ModelSchema: RestModel {
+    /// The struct name, used to name generated schemas.
+    fn get_model_name() -> &'static str;
+
+    /// Methods enabled via `with(..)`.
+    fn get_operations() -> &'static [Operation];
+
+    /// JSON Schema of `data`, derived from the struct fields.
+    fn get_data_schema() -> Value;
+}
+
+/// Standalone JSON Schema (draft 2020-12) of `T`'s `data`, e.g. to validate
+/// payloads in other services.
+pub fn json_schema<T: ModelSchema>() -> Value {
+    let mut schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": T::get_model_name(),
+    });
+    if let (Some(schema), Value::Object(data)) = (schema.as_object_mut(), T::get_data_schema()) {
+        schema.extend(data);
+    }
+    schema
+}
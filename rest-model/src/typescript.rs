@@ -0,0 +1,341 @@
+use serde_json::Value;
+
+use crate::{ModelSchema, Operation};
+
+/// Shared declarations mirroring the serde encoding of `rest_model` types;
+/// `Condition` is externally tagged, e.g. `{ Eq: ["name", "Tom"] }`.
+const PRELUDE: &str = r#"// Generated by rest-model. Do not edit.
+
+export interface Doc<T> {
+  _id: string;
+  data: T;
+  _created_at: number;
+  _updated_at: number;
+  /** Referenced documents by reference field, when requested with `expand`. */
+  _expanded?: Record<string, Doc<unknown> | null>;
+  /** Search fields matching `q`, HTML-escaped with the matches wrapped in `<mark>`. */
+  _highlights?: Record<string, unknown>;
+}
+
+export interface Pagination {
+  total_count: number;
+  total_pages: number;
+  current_page: number;
+  items_per_page: number;
+}
+
+export interface PaginationResult<T> {
+  items: Doc<T>[];
+  pagination: Pagination;
+}
+
+export type Condition =
+  | { And: Condition[] }
+  | { Or: Condition[] }
+  | { Not: Condition }
+  | { Regex: [string, string] }
+  | { Regexi: [string, string] }
+  | { Eq: [string, unknown] }
+  | { Ne: [string, unknown] }
+  | { Gt: [string, unknown] }
+  | { Lt: [string, unknown] }
+  | { Gte: [string, unknown] }
+  | { Lte: [string, unknown] }
+  | { In: [string, unknown[]] }
+  | { Nin: [string, unknown[]] };
+
+export interface PaginationParams {
+  page?: number;
+  limit?: number;
+  /** Field paths prefixed with `+` (ascending) or `-` (descending), each optionally followed by `:text`, `:number` or `:timestamp` and `:nulls_first` or `:nulls_last`, e.g. `+name-age:number`. */
+  sort?: string;
+  filter?: Condition;
+  custom?: string;
+  /** Comma-separated reference fields whose documents to embed, e.g. `customer_id`. */
+  expand?: string;
+  /** Full-text search of the model's search fields, e.g. `"red apple" or pear -green`. */
+  q?: string;
+}
+
+export interface PatchParams {
+  filter: Condition;
+  patch: Record<string, unknown>;
+}
+
+export interface DeleteParams {
+  filter: Condition;
+}
+
+export type Returning = "none" | "ids" | "docs";
+
+export interface UpsertResult<T = unknown> {
+  created_count: number;
+  updated_count: number;
+  ids?: string[];
+  docs?: Doc<T>[];
+}
+
+export type OnConflict = "fail" | "skip";
+
+export interface InsertResult {
+  created_count: number;
+  created_ids: string[];
+  skipped_ids: string[];
+}
+
+export interface UpdateResult<T = unknown> {
+  updated_count: number;
+  ids?: string[];
+  docs?: Doc<T>[];
+}
+
+export interface DeleteResult<T = unknown> {
+  deleted_count: number;
+  ids?: string[];
+  docs?: Doc<T>[];
+  /** Documents deleted by `cascade` references, by table. */
+  cascaded?: Record<string, number>;
+  /** Documents whose `set_null` reference was cleared, by table. */
+  nullified?: Record<string, number>;
+}
+
+export class RestModelClient {
+  constructor(
+    public baseUrl: string,
+    public init: RequestInit = {},
+  ) {}
+
+  async request<R>(
+    method: string,
+    path: string,
+    query?: URLSearchParams,
+    body?: unknown,
+  ): Promise<R> {
+    const response = await this.send(method, path, query, body);
+    return (await response.json()) as R;
+  }
+
+  /** GETs a page, sent either as the items with `X-*` pagination headers or as a `PaginationResult`. */
+  async page<T>(path: string, query?: URLSearchParams): Promise<PaginationResult<T>> {
+    const response = await this.send("GET", path, query);
+    const body = await response.json();
+    if (!Array.isArray(body)) {
+      return body as PaginationResult<T>;
+    }
+    const header = (name: string): number => {
+      const value = response.headers.get(name);
+      if (value === null) {
+        throw new Error(`GET ${path}: missing header ${name}`);
+      }
+      return Number(value);
+    };
+    return {
+      items: body as Doc<T>[],
+      pagination: {
+        total_count: header("X-Total-Count"),
+        total_pages: header("X-Total-Pages"),
+        current_page: header("X-Current-Page"),
+        items_per_page: header("X-Items-Per-Page"),
+      },
+    };
+  }
+
+  private async send(
+    method: string,
+    path: string,
+    query?: URLSearchParams,
+    body?: unknown,
+  ): Promise<Response> {
+    const search = query && query.toString() ? `?${query}` : "";
+    const headers = new Headers(this.init.headers);
+    if (body !== undefined) {
+      headers.set("Content-Type", "application/json");
+    }
+    const response = await fetch(`${this.baseUrl}${path}${search}`, {
+      ...this.init,
+      method,
+      headers,
+      body: body === undefined ? undefined : JSON.stringify(body),
+    });
+    if (!response.ok) {
+      throw new Error(`${method} ${path} failed: ${response.status} ${await response.text()}`);
+    }
+    return response;
+  }
+}
+
+/** `PaginationParams` as query parameters; `filter` is JSON-encoded. */
+export function paginationQuery(params: PaginationParams): URLSearchParams {
+  const query = new URLSearchParams();
+  if (params.page !== undefined) query.set("page", String(params.page));
+  if (params.limit !== undefined) query.set("limit", String(params.limit));
+  if (params.sort !== undefined) query.set("sort", params.sort);
+  if (params.filter !== undefined) query.set("filter", JSON.stringify(params.filter));
+  if (params.custom !== undefined) query.set("custom", params.custom);
+  if (params.expand !== undefined) query.set("expand", params.expand);
+  if (params.q !== undefined) query.set("q", params.q);
+  return query;
+}
+
+/** `?returning=..`, empty unless ids or docs are requested. */
+export function returningQuery(returning: Returning): URLSearchParams {
+  return new URLSearchParams(returning === "none" ? {} : { returning });
+}
+"#;
+
+/// TypeScript declarations and a `fetch`-based client for one or more
+/// `#[rest_model]` types, e.g. written to a file from a build script.
+pub struct TypeScript {
+    output: String,
+}
+
+impl Default for TypeScript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeScript {
+    pub fn new() -> Self {
+        Self {
+            output: PRELUDE.to_string(),
+        }
+    }
+
+    /// Adds an interface for `T` and a `{T}Api` class for its enabled methods,
+    /// served under `path`, e.g. `/guests`.
+    pub fn model<T: ModelSchema>(mut self, path: &str) -> Self {
+        let name = T::get_model_name();
+        let path = path.trim_end_matches('/');
+        self.output.push_str(&format!(
+            "\nexport interface {} {}\n",
+            name,
+            ts_type(&T::get_data_schema(), 0)
+        ));
+
+        let mut methods = vec![];
+        for operation in T::get_operations() {
+            methods.push(match operation {
+                Operation::Get => format!(
+                    "  get(params: PaginationParams = {{}}): Promise<PaginationResult<{n}>> {{\n    return this.client.page(this.path, paginationQuery(params));\n  }}\n",
+                    n = name
+                ),
+                Operation::GetWithId => format!(
+                    "  getWithId(id: string): Promise<Doc<{n}>> {{\n    return this.client.request(\"GET\", `${{this.path}}/${{encodeURIComponent(id)}}`);\n  }}\n",
+                    n = name
+                ),
+                Operation::Post => format!(
+                    "  post(items: Doc<{n}>[], onConflict: OnConflict = \"fail\"): Promise<InsertResult> {{\n    return this.client.request(\"POST\", this.path, new URLSearchParams({{ on_conflict: onConflict }}), items);\n  }}\n",
+                    n = name
+                ),
+                Operation::Put => format!(
+                    "  put(items: Doc<{n}>[], returning: Returning = \"none\"): Promise<UpsertResult<{n}>> {{\n    return this.client.request(\"PUT\", this.path, returningQuery(returning), items);\n  }}\n",
+                    n = name
+                ),
+                Operation::Patch => format!(
+                    "  patch(params: PatchParams, returning: Returning = \"none\"): Promise<UpdateResult<{n}>> {{\n    return this.client.request(\"PATCH\", this.path, returningQuery(returning), params);\n  }}\n",
+                    n = name
+                ),
+                Operation::Delete => format!(
+                    "  delete(params: DeleteParams, returning: Returning = \"none\"): Promise<DeleteResult<{n}>> {{\n    return this.client.request(\"DELETE\", this.path, returningQuery(returning), params);\n  }}\n",
+                    n = name
+                ),
+            });
+        }
+        self.output.push_str(&format!(
+            "\nexport class {n}Api {{\n  constructor(\n    private client: RestModelClient,\n    private path: string = {p},\n  ) {{}}\n{m}}}\n",
+            n = name,
+            p = serde_json::to_string(path).unwrap(),
+            m = methods
+                .iter()
+                .map(|m| format!("\n{}", m))
+                .collect::<String>()
+        ));
+        self
+    }
+
+    pub fn build(self) -> String {
+        self.output
+    }
+}
+
+/// TypeScript module for a single model served at `/{table_name}`.
+pub fn typescript<T: ModelSchema>() -> String {
+    TypeScript::new()
+        .model::<T>(&format!("/{}", T::get_table_name()))
+        .build()
+}
+
+/// The TypeScript type for a JSON Schema generated by `#[rest_model]`.
+fn ts_type(schema: &Value, indent: usize) -> String {
+    if let Some(branches) = schema.get("anyOf").and_then(|a| a.as_array()) {
+        return branches
+            .iter()
+            .map(|b| ts_type(b, indent))
+            .collect::<Vec<_>>()
+            .join(" | ");
+    }
+    match schema.get("type").and_then(|t| t.as_str()) {
+        Some("string") => "string".to_string(),
+        Some("integer") | Some("number") => "number".to_string(),
+        Some("boolean") => "boolean".to_string(),
+        Some("null") => "null".to_string(),
+        Some("array") => {
+            let items = ts_type(schema.get("items").unwrap_or(&Value::Null), indent);
+            if items.contains(' ') {
+                format!("({})[]", items)
+            } else {
+                format!("{}[]", items)
+            }
+        }
+        Some("object") => match schema.get("properties").and_then(|p| p.as_object()) {
+            Some(properties) => {
+                let required = schema
+                    .get("required")
+                    .and_then(|r| r.as_array())
+                    .map(|r| r.iter().filter_map(|k| k.as_str()).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                let pad = "  ".repeat(indent + 1);
+                let fields = properties
+                    .iter()
+                    .map(|(key, property)| {
+                        format!(
+                            "{}{}{}: {};\n",
+                            pad,
+                            ts_key(key),
+                            if required.contains(&key.as_str()) {
+                                ""
+                            } else {
+                                "?"
+                            },
+                            ts_type(property, indent + 1)
+                        )
+                    })
+                    .collect::<String>();
+                format!("{{\n{}{}}}", fields, "  ".repeat(indent))
+            }
+            None => format!(
+                "Record<string, {}>",
+                schema
+                    .get("additionalProperties")
+                    .map(|a| ts_type(a, indent))
+                    .unwrap_or_else(|| "unknown".to_string())
+            ),
+        },
+        _ => "unknown".to_string(),
+    }
+}
+
+/// Quotes property names that aren't plain identifiers.
+fn ts_key(key: &str) -> String {
+    let mut chars = key.chars();
+    let plain = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if plain {
+        key.to_string()
+    } else {
+        serde_json::to_string(key).unwrap()
+    }
+}